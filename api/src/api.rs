@@ -7,12 +7,50 @@ rpc_api! {
 
     rpc create(CreateGameRequest) -> (CreateGameResponse);
 
+    rpc list_games(ListGamesRequest) -> (ListGamesResponse);
+
     rpc join(JoinGameRequest) -> (JoinGameResponse);
 
     rpc play(PlayHandRequest) -> (PlayHandResponse);
 
+    rpc commit_seed(CommitSeedRequest) -> (CommitSeedResponse);
+
+    rpc reveal_seed(RevealSeedRequest) -> (RevealSeedResponse);
+
     rpc take_action(TakeActionRequest) -> (TakeActionResponse);
 
+    rpc queue_action(QueueActionRequest) -> (QueueActionResponse);
+
+    rpc sit_out(SitOutRequest) -> (SitOutResponse);
+
+    rpc sit_in(SitInRequest) -> (SitInResponse);
+
+    rpc top_up(TopUpRequest) -> (TopUpResponse);
+
+    rpc rebuy(RebuyRequest) -> (RebuyResponse);
+
+    rpc add_on(AddOnRequest) -> (AddOnResponse);
+
+    rpc get_player_state(PlayerStateRequest) -> (PlayerState);
+
+    rpc get_public_state(PublicStateRequest) -> (PublicState);
+
     rpc leave(WithdrawRequest) -> (WithdrawResponse);
 
+    rpc withdraw_rake(WithdrawRakeRequest) -> (WithdrawRakeResponse);
+
+    rpc claim_timeout(ClaimTimeoutRequest) -> (ClaimTimeoutResponse);
+
+    rpc create_tournament(CreateTournamentRequest) -> (CreateTournamentResponse);
+
+    rpc register(RegisterRequest) -> (RegisterResponse);
+
+    rpc start_tournament(StartTournamentRequest) -> (StartTournamentResponse);
+
+    rpc balance_tables(BalanceTablesRequest) -> (BalanceTablesResponse);
+
+    rpc claim_prize(ClaimPrizeRequest) -> (ClaimPrizeResponse);
+
+    rpc get_tournament_state(TournamentStateRequest) -> (TournamentState);
+
 }
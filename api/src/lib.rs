@@ -1,4 +1,8 @@
 extern crate protobuf;
+extern crate rand;
+extern crate rs_poker;
+extern crate serde_cbor;
+extern crate sha2;
 
 #[macro_use]
 extern crate ekiden_core_common;
@@ -6,5 +10,7 @@ extern crate ekiden_core_common;
 #[macro_use]
 mod api;
 mod generated;
+mod shuffle;
 
 pub use generated::api::*;
+pub use shuffle::{commit_seed, derive_seed, ordered_deck, shuffle_deck, verify_shuffle};
@@ -0,0 +1,239 @@
+//Derivation of the per-hand deck from the shuffle seeds, shared by the contract and
+//by clients that want to audit a hand after it is over.
+use rand::{ChaChaRng, Rng, SeedableRng};
+use rs_poker::core::{Card, Suit, Value};
+use serde_cbor;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use generated::api::{GameVariant, HandHistory};
+
+//Combines the enclave's random bytes, the players' combined seed and the hand number
+//into the seed for a single hand.
+pub fn derive_seed(enclave_seed: &[u8; 32], player_seed: &[u8; 32], hand_number: u64) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    hasher.input(enclave_seed);
+    hasher.input(player_seed);
+    let mut counter = [0u8; 8];
+    for i in 0..8 {
+        counter[i] = (hand_number >> (8 * i)) as u8;
+    }
+    hasher.input(&counter);
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(hasher.result().as_slice());
+    seed
+}
+
+//Commitment to a hand's seed, published before any card is dealt.
+pub fn commit_seed(seed: &[u8; 32]) -> Vec<u8> {
+    Sha256::digest(seed).to_vec()
+}
+
+//A full deck in a fixed order, so that the same seed always gives the same shuffle.
+pub fn ordered_deck() -> Vec<Card> {
+    let mut deck = Vec::new();
+    for value in Value::values().iter() {
+        for suit in Suit::suits().iter() {
+            deck.push(Card {
+                value: *value,
+                suit: *suit,
+            });
+        }
+    }
+    deck
+}
+
+//Shuffles a full deck with a ChaCha stream keyed by `seed`.
+pub fn shuffle_deck(seed: &[u8; 32]) -> Vec<Card> {
+    let mut key = [0u32; 8];
+    for i in 0..8 {
+        for j in 0..4 {
+            key[i] |= (seed[4 * i + j] as u32) << (8 * j);
+        }
+    }
+    let mut rng = ChaChaRng::from_seed(&key[..]);
+    let mut deck = ordered_deck();
+    rng.shuffle(&mut deck);
+    deck
+}
+
+//Re-derives the shuffle from a published hand history and checks it against the
+//commitment made before dealing and the deck the contract reports it used, then
+//checks the board and the hands shown down against that deck.
+pub fn verify_shuffle(history: &HandHistory) -> bool {
+    if history.get_enclave_seed().len() != 32 {
+        return false;
+    }
+    let mut enclave_seed = [0; 32];
+    enclave_seed.copy_from_slice(history.get_enclave_seed());
+
+    let mut player_seed = [0; 32];
+    for reveal in history.get_reveals().iter() {
+        if reveal.get_seed().len() != 32 {
+            return false;
+        }
+        for i in 0..32 {
+            player_seed[i] ^= reveal.get_seed()[i];
+        }
+    }
+
+    let seed = derive_seed(&enclave_seed, &player_seed, history.get_hand_number());
+    if commit_seed(&seed) != history.get_seed_commitment() {
+        return false;
+    }
+    let deck: Vec<Card> = match serde_cbor::from_slice(history.get_deck()) {
+        Ok(deck) => deck,
+        Err(_) => return false,
+    };
+    if shuffle_deck(&seed) != deck {
+        return false;
+    }
+    verify_deal(history, &deck)
+}
+
+//Deals `deck` again in the recorded order and compares the result with the board and
+//the hands shown down. Cards are dealt from the end of the deck.
+fn verify_deal(history: &HandHistory, deck: &[Card]) -> bool {
+    let stud = history.get_variant() == GameVariant::SEVEN_CARD_STUD;
+    let mut cards = deck.iter().rev();
+    let mut hands: HashMap<String, (Vec<Card>, Vec<Card>)> = HashMap::new();
+    for (i, round) in history.get_rounds().iter().enumerate() {
+        //In stud the third to sixth cards are dealt face up
+        let face_up = stud && i >= 2 && i < 6;
+        for addr in round.get_players().iter() {
+            let card = match cards.next() {
+                Some(card) => card.clone(),
+                None => return false,
+            };
+            let hand = hands.entry(addr.clone()).or_insert((Vec::new(), Vec::new()));
+            if face_up {
+                hand.1.push(card);
+            } else {
+                hand.0.push(card);
+            }
+        }
+    }
+
+    let board: Vec<Card> = match serde_cbor::from_slice(history.get_board()) {
+        Ok(board) => board,
+        Err(_) => return false,
+    };
+    //A card is burned before each of the flop, turn and river. Stud has no board.
+    let mut expected = Vec::new();
+    if !stud {
+        for count in [3, 1, 1].iter() {
+            if expected.len() >= board.len() {
+                break;
+            }
+            cards.next();
+            expected.extend(cards.by_ref().take(*count).cloned());
+        }
+    }
+    if expected != board {
+        return false;
+    }
+
+    for shown in history.get_shown().iter() {
+        let down: Vec<Card> = match serde_cbor::from_slice(shown.get_cards()) {
+            Ok(cards) => cards,
+            Err(_) => return false,
+        };
+        let up: Vec<Card> = match serde_cbor::from_slice(shown.get_up_cards()) {
+            Ok(cards) => cards,
+            Err(_) => return false,
+        };
+        match hands.get(shown.get_addr()) {
+            Some(hand) if hand.0 == down && hand.1 == up => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generated::api::{DealRound, SeedReveal, ShownHand};
+
+    //The `i`th card dealt from the deck in `history`.
+    fn dealt(history: &HandHistory, i: usize) -> Card {
+        let deck: Vec<Card> = serde_cbor::from_slice(history.get_deck()).unwrap();
+        deck[deck.len() - 1 - i].clone()
+    }
+
+    fn show(history: &mut HandHistory, addr: &str, cards: Vec<Card>) {
+        let mut shown = ShownHand::new();
+        shown.set_addr(addr.to_string());
+        shown.set_cards(serde_cbor::to_vec(&cards).unwrap());
+        shown.set_up_cards(serde_cbor::to_vec(&Vec::<Card>::new()).unwrap());
+        history.mut_shown().push(shown);
+    }
+
+    //A heads-up hold'em hand shuffled and dealt without the board or the hands
+    //shown down. Bob is dealt to first.
+    fn holdem_hand() -> HandHistory {
+        let enclave_seed = [1; 32];
+        let mut history = HandHistory::new();
+        history.set_hand_number(7);
+        history.set_enclave_seed(enclave_seed.to_vec());
+        for &(addr, seed) in [("alice", 3), ("bob", 1)].iter() {
+            let mut reveal = SeedReveal::new();
+            reveal.set_addr(addr.to_string());
+            reveal.set_seed(vec![seed; 32]);
+            history.mut_reveals().push(reveal);
+        }
+        let seed = derive_seed(&enclave_seed, &[2; 32], 7);
+        history.set_seed_commitment(commit_seed(&seed));
+        history.set_deck(serde_cbor::to_vec(&shuffle_deck(&seed)).unwrap());
+        history.set_variant(GameVariant::TEXAS_HOLDEM);
+        for _ in 0..2 {
+            let mut round = DealRound::new();
+            round.mut_players().push("bob".to_string());
+            round.mut_players().push("alice".to_string());
+            history.mut_rounds().push(round);
+        }
+        history
+    }
+
+    //Deals bob, alice, bob, alice, then burns a card before the flop, turn and river.
+    fn showdown(board: &[usize], bob: &[usize], alice: &[usize]) -> HandHistory {
+        let mut history = holdem_hand();
+        let board: Vec<Card> = board.iter().map(|i| dealt(&history, *i)).collect();
+        history.set_board(serde_cbor::to_vec(&board).unwrap());
+        let bob = bob.iter().map(|i| dealt(&history, *i)).collect();
+        let alice = alice.iter().map(|i| dealt(&history, *i)).collect();
+        show(&mut history, "bob", bob);
+        show(&mut history, "alice", alice);
+        history
+    }
+
+    #[test]
+    fn accepts_the_hand_as_dealt() {
+        assert!(verify_shuffle(&showdown(&[5, 6, 7, 9, 11], &[0, 2], &[1, 3])));
+    }
+
+    #[test]
+    fn accepts_a_hand_that_ended_on_the_flop() {
+        let mut history = holdem_hand();
+        let board: Vec<Card> = [5, 6, 7].iter().map(|i| dealt(&history, *i)).collect();
+        history.set_board(serde_cbor::to_vec(&board).unwrap());
+        assert!(verify_shuffle(&history));
+    }
+
+    #[test]
+    fn rejects_a_board_dealt_without_burns() {
+        assert!(!verify_shuffle(&showdown(&[4, 5, 6, 7, 8], &[0, 2], &[1, 3])));
+    }
+
+    #[test]
+    fn rejects_swapped_hole_cards() {
+        assert!(!verify_shuffle(&showdown(&[5, 6, 7, 9, 11], &[1, 3], &[0, 2])));
+    }
+
+    #[test]
+    fn rejects_a_tampered_seed() {
+        let mut history = showdown(&[5, 6, 7, 9, 11], &[0, 2], &[1, 3]);
+        history.mut_reveals()[0].set_seed(vec![4; 32]);
+        assert!(!verify_shuffle(&history));
+    }
+}
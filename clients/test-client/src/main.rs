@@ -2,8 +2,11 @@
 
 #[macro_use]
 extern crate clap;
+extern crate ed25519_dalek;
 extern crate futures;
+extern crate protobuf;
 extern crate rand;
+extern crate sha2;
 extern crate tokio_core;
 
 #[macro_use]
@@ -14,7 +17,10 @@ extern crate ekiden_rpc_client;
 extern crate poker_api;
 
 use clap::{App, Arg};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use protobuf::Message;
 use rand::{Rng};
+use sha2::{Digest, Sha256, Sha512};
 
 use ekiden_rpc_client::create_client_rpc;
 use poker_api::with_api;
@@ -23,6 +29,48 @@ with_api! {
     create_client_rpc!(poker, poker_api, api);
 }
 
+/// Signs a request on behalf of the player owning `keypair`. Every mutating request
+/// carries the player's next nonce and the number of the current hand.
+macro_rules! sign {
+    ($request:expr, $keypair:expr, $nonce:expr, $hand_number:expr) => {{
+        let keypair = $keypair;
+        $request.set_nonce($nonce);
+        $request.set_hand_number($hand_number);
+        $request.set_sender(address_of(&keypair));
+        $request.set_public_key(keypair.public.to_bytes().to_vec());
+        let signature = keypair.sign::<Sha512>(&$request.write_to_bytes().unwrap());
+        $request.set_signature(signature.to_bytes().to_vec());
+    }};
+}
+
+/// Deterministic key pair for a named test player.
+fn keypair(name: &str) -> Keypair {
+    let secret = SecretKey::from_bytes(Sha256::digest(name.as_bytes()).as_slice()).unwrap();
+    let public = PublicKey::from_secret::<Sha512>(&secret);
+    Keypair { secret, public }
+}
+
+/// Address the contract assigns to a key pair: the public key in lowercase hex.
+fn address_of(keypair: &Keypair) -> String {
+    keypair
+        .public
+        .to_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns the id of the most recently created table.
+fn latest_game_id<Backend>(client: &mut poker::Client<Backend>) -> u64
+where
+    Backend: ekiden_rpc_client::backend::ContractClientBackend,
+{
+    let response = ekiden_rpc_client::FutureExtra::wait(
+        client.list_games(poker::ListGamesRequest::new()),
+    ).unwrap();
+    response.get_games().last().unwrap().get_game_id()
+}
+
 /// Initializes the poker scenario.
 fn init<Backend>(client: &mut poker::Client<Backend>, _runs: usize, _threads: usize)
 where
@@ -34,15 +82,15 @@ where
     request.set_max_players(4);
     request.set_time_per_turn(4);
 
-    ekiden_rpc_client::FutureExtra::wait(client.create(request)).unwrap();
+    let response = ekiden_rpc_client::FutureExtra::wait(client.create(request)).unwrap();
+    let game_id = response.get_game_id();
 
-    let mut rng = rand::thread_rng();
     // Check balances.
     let response = ekiden_rpc_client::FutureExtra::wait(client.join({
         let mut request = poker::JoinGameRequest::new();
-        request.set_sender("client1".to_string());
+        request.set_game_id(game_id);
         request.set_deposit(5);
-        request.set_seed(vec![rng.gen::<u32>(); 32]);
+        sign!(request, keypair("client1"), 1, 0);
         request
     })).unwrap();
     assert_eq!(response.get_joined(), true);
@@ -53,13 +101,14 @@ fn scenario<Backend>(client: &mut poker::Client<Backend>)
 where
     Backend: ekiden_rpc_client::backend::ContractClientBackend,
 {
+    let game_id = latest_game_id(client);
+
     //Second player joins
-    let mut rng = rand::thread_rng();
     let response = ekiden_rpc_client::FutureExtra::wait(client.join({
         let mut request = poker::JoinGameRequest::new();
-        request.set_sender("client2".to_string());
+        request.set_game_id(game_id);
         request.set_deposit(4);
-        request.set_seed(vec![rng.gen::<u32>(), 32]);
+        sign!(request, keypair("client2"), 1, 0);
         request
     })).unwrap();
     assert_eq!(response.get_joined(), true);
@@ -67,33 +116,75 @@ where
     //Start game
     let response = ekiden_rpc_client::FutureExtra::wait(client.play({
         let mut request = poker::PlayHandRequest::new();
-        request.set_sender("client1".to_string());
+        request.set_game_id(game_id);
+        sign!(request, keypair("client1"), 2, 0);
         request
     })).unwrap();
     assert_eq!(response.get_success(), true);
 
-    //both check
+    //Both commit to a seed, then reveal it
+    let mut rng = rand::thread_rng();
+    let seeds = vec![
+        ("client1", 3, rng.gen::<[u8; 32]>()),
+        ("client2", 2, rng.gen::<[u8; 32]>()),
+    ];
+    for &(name, nonce, ref seed) in seeds.iter() {
+        let response = ekiden_rpc_client::FutureExtra::wait(client.commit_seed({
+            let mut request = poker::CommitSeedRequest::new();
+            request.set_game_id(game_id);
+            request.set_commitment(Sha256::digest(seed).to_vec());
+            sign!(request, keypair(name), nonce, 0);
+            request
+        })).unwrap();
+        assert_eq!(response.get_success(), true);
+    }
+    for &(name, nonce, ref seed) in seeds.iter() {
+        let response = ekiden_rpc_client::FutureExtra::wait(client.reveal_seed({
+            let mut request = poker::RevealSeedRequest::new();
+            request.set_game_id(game_id);
+            request.set_seed(seed.to_vec());
+            sign!(request, keypair(name), nonce + 1, 0);
+            request
+        })).unwrap();
+        assert_eq!(response.get_success(), true);
+    }
+
+    //Heads-up the button posts the small blind and acts first before the flop:
+    //client1 calls and client2 checks the big blind
     let response = ekiden_rpc_client::FutureExtra::wait(client.take_action({
         let mut request = poker::TakeActionRequest::new();
-        request.set_sender("client1".to_string());
-        request.set_action("Check".to_string());
+        request.set_game_id(game_id);
+        request.set_action(poker::ActionType::CALL);
+        sign!(request, keypair("client1"), 5, 1);
         request
     })).unwrap();
     assert_eq!(response.get_success(), true);
 
     let response = ekiden_rpc_client::FutureExtra::wait(client.take_action({
         let mut request = poker::TakeActionRequest::new();
-        request.set_sender("client2".to_string());
-        request.set_action("Check".to_string());
+        request.set_game_id(game_id);
+        request.set_action(poker::ActionType::CHECK);
+        sign!(request, keypair("client2"), 4, 1);
         request
     })).unwrap();
     assert_eq!(response.get_success(), true);
 
-    //fold
+    //After the flop the big blind acts first: client2 bets 2 and client1 folds
     let response = ekiden_rpc_client::FutureExtra::wait(client.take_action({
         let mut request = poker::TakeActionRequest::new();
-        request.set_sender("client1".to_string());
-        request.set_action("Fold".to_string());
+        request.set_game_id(game_id);
+        request.set_action(poker::ActionType::BET);
+        request.set_amount(2);
+        sign!(request, keypair("client2"), 5, 1);
+        request
+    })).unwrap();
+    assert_eq!(response.get_success(), true);
+
+    let response = ekiden_rpc_client::FutureExtra::wait(client.take_action({
+        let mut request = poker::TakeActionRequest::new();
+        request.set_game_id(game_id);
+        request.set_action(poker::ActionType::FOLD);
+        sign!(request, keypair("client1"), 6, 1);
         request
     })).unwrap();
     assert_eq!(response.get_success(), true);
@@ -104,17 +195,21 @@ fn finalize<Backend>(client: &mut poker::Client<Backend>, _runs: usize, _threads
 where
     Backend: ekiden_rpc_client::backend::ContractClientBackend,
 {
-    //both withdraw, verify final balance
+    let game_id = latest_game_id(client);
+
+    //Both withdraw. client2 won client1's 2 chips and got the uncalled bet back.
     let response = ekiden_rpc_client::FutureExtra::wait(client.leave({
         let mut request = poker::WithdrawRequest::new();
-        request.set_sender("client1".to_string());
+        request.set_game_id(game_id);
+        sign!(request, keypair("client1"), 7, 1);
         request
     })).unwrap();
     assert_eq!(response.get_balance(), 3);
 
     let response = ekiden_rpc_client::FutureExtra::wait(client.leave({
         let mut request = poker::WithdrawRequest::new();
-        request.set_sender("client2".to_string());
+        request.set_game_id(game_id);
+        sign!(request, keypair("client2"), 6, 1);
         request
     })).unwrap();
     assert_eq!(response.get_balance(), 6);
@@ -0,0 +1,61 @@
+//Authentication of RPC callers. Requests carry the sender's ed25519 public key and a
+//signature over the serialized request with the `signature` field left empty. The
+//sender's address is the public key in lowercase hex, so a valid signature proves
+//the claimed sender made the request.
+use ed25519_dalek::{PublicKey, Signature};
+use ekiden_core_common::{Address, ContractError, Result};
+use sha2::Sha512;
+
+//Checks the signature on a request and returns the authenticated sender's address.
+macro_rules! authenticate {
+    ($request:expr) => {{
+        let mut unsigned = $request.clone();
+        unsigned.clear_signature();
+        auth::verify_sender(
+            $request.get_sender(),
+            $request.get_public_key(),
+            $request.get_signature(),
+            &unsigned
+                .write_to_bytes()
+                .expect("Unable to serialize request."),
+        )?
+    }};
+}
+
+//Address of the player who owns `public_key`.
+pub fn address_of(public_key: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut address = String::with_capacity(2 * public_key.len());
+    for byte in public_key {
+        address.push(HEX[(byte >> 4) as usize] as char);
+        address.push(HEX[(byte & 0xf) as usize] as char);
+    }
+    address
+}
+
+//Verifies that `signature` over `message` was made with `public_key`, and that the
+//key belongs to `sender`.
+pub fn verify_sender(
+    sender: &str,
+    public_key: &[u8],
+    signature: &[u8],
+    message: &[u8],
+) -> Result<Address> {
+    if address_of(public_key) != sender {
+        return Err(ContractError::new(
+            "Sender does not match the key that signed the request.",
+        ));
+    }
+    let key = match PublicKey::from_bytes(public_key) {
+        Ok(key) => key,
+        Err(_) => return Err(ContractError::new("Invalid public key.")),
+    };
+    let signature = match Signature::from_bytes(signature) {
+        Ok(signature) => signature,
+        Err(_) => return Err(ContractError::new("Invalid request signature.")),
+    };
+    if !key.verify::<Sha512>(message, &signature) {
+        return Err(ContractError::new("Invalid request signature."));
+    }
+    Ok(Address::from(sender.to_string()))
+}
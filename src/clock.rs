@@ -0,0 +1,36 @@
+//Time sources used to enforce `time_per_turn`.
+use ekiden_core_trusted::time::get_trusted_time;
+
+pub trait Clock {
+    //Current time in seconds.
+    fn now(&self) -> u64;
+}
+
+//Trusted time supplied by the Ekiden runtime.
+pub struct EnclaveClock;
+
+impl Clock for EnclaveClock {
+    fn now(&self) -> u64 {
+        get_trusted_time()
+    }
+}
+
+//A clock stuck at a fixed time, so turn timeouts can be exercised in tests.
+#[cfg(test)]
+pub struct MockClock {
+    pub time: u64,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(time: u64) -> MockClock {
+        MockClock { time }
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.time
+    }
+}
@@ -0,0 +1,375 @@
+//Tournaments played over several tables. The coordinator seats the entrants, moves
+//players to keep the tables balanced as others are knocked out, breaks tables until
+//only the final table is left, and places and pays every player.
+use ekiden_core_common::{Address, Contract, ContractError, Result};
+
+use poker_api::{CreateGameRequest, Finish, TournamentState};
+use poker_contract::{Player, PokerContract};
+use tournament::{check_nonce, finish, serialize_nonces};
+use std::collections::HashMap;
+
+pub struct Coordinator {
+    tournament_id: u64,
+    //Options every table is created with. `max_players` is the size of each table.
+    options: CreateGameRequest,
+    entrants: Vec<String>,
+    prize_pool: u64,
+    //Tables in play, including tables that have been broken
+    game_ids: Vec<u64>,
+    //Finishing places, in the order players were knocked out
+    results: Vec<Finish>,
+    //Last nonce used by each entrant
+    nonces: HashMap<String, u64>,
+    started: bool,
+    over: bool,
+}
+
+impl Coordinator {
+    //Creates a tournament whose tables are all created with `options`, which must
+    //describe a tournament table.
+    pub fn new(tournament_id: u64, options: &CreateGameRequest) -> Result<Coordinator> {
+        if options.get_tournament().get_levels().is_empty() {
+            return Err(ContractError::new("A tournament needs a blind schedule."));
+        }
+        //Checks the rest of the options the same way a table would
+        PokerContract::new(0, options)?;
+
+        return Ok(Coordinator {
+            tournament_id,
+            options: options.clone(),
+            entrants: Vec::new(),
+            prize_pool: 0,
+            game_ids: Vec::new(),
+            results: Vec::new(),
+            nonces: HashMap::new(),
+            started: false,
+            over: false,
+        });
+    }
+
+    //Rejects requests that are not meant for this tournament, or that reuse a nonce.
+    pub fn check_request(
+        &mut self,
+        msg_sender: &Address,
+        tournament_id: u64,
+        nonce: u64,
+    ) -> Result<()> {
+        if tournament_id != self.tournament_id {
+            return Err(ContractError::new("Request is for a different tournament."));
+        }
+        check_nonce(&mut self.nonces, msg_sender, nonce)
+    }
+
+    //Registers a player for the tournament, adding the buy-in to the prize pool.
+    pub fn register(&mut self, msg_sender: &Address, deposit: u64) -> Result<()> {
+        if self.started {
+            return Err(ContractError::new(
+                "Registration for this tournament has closed.",
+            ));
+        }
+        if deposit != self.options.get_tournament().get_buy_in() {
+            return Err(ContractError::new(
+                "The deposit must be the tournament buy-in.",
+            ));
+        }
+        if self.entrants.contains(&msg_sender.to_string()) {
+            return Err(ContractError::new("Player is already registered."));
+        }
+        self.entrants.push(msg_sender.to_string());
+        self.prize_pool += deposit;
+        Ok(())
+    }
+
+    //Number of tables needed to seat every entrant.
+    pub fn tables_needed(&self) -> u64 {
+        let table_size = self.options.get_max_players();
+        (self.entrants.len() as u64 + table_size - 1) / table_size
+    }
+
+    //Closes registration and seats the entrants over `tables_needed` new tables, one
+    //for each of `game_ids`, dealing them out in turn so the tables are as even as
+    //possible. Any entrant can start the tournament once two have registered. The
+    //blind clock of every table starts at `now`.
+    pub fn start(
+        &mut self,
+        msg_sender: &Address,
+        game_ids: &[u64],
+        now: u64,
+    ) -> Result<Vec<PokerContract>> {
+        if self.started {
+            return Err(ContractError::new("The tournament has already started."));
+        }
+        if !self.entrants.contains(&msg_sender.to_string()) {
+            return Err(ContractError::new("This player is not registered."));
+        }
+        if self.entrants.len() < 2 {
+            return Err(ContractError::new("A tournament needs at least two players."));
+        }
+        if game_ids.len() as u64 != self.tables_needed() {
+            return Err(ContractError::new("Wrong number of tables."));
+        }
+        let mut tables = Vec::new();
+        for game_id in game_ids {
+            let mut table = PokerContract::new(*game_id, &self.options)?;
+            table.set_coordinator(self.tournament_id, now);
+            tables.push(table);
+        }
+        let starting_stack = self.options.get_tournament().get_starting_stack();
+        for (i, addr) in self.entrants.iter().enumerate() {
+            let player = Player::new(Address::from(addr.clone()), starting_stack);
+            tables[i % game_ids.len()].seat_player(player)?;
+        }
+        self.game_ids = game_ids.to_vec();
+        self.started = true;
+        Ok(tables)
+    }
+
+    //Places the players knocked out since the last call, brings every table up to the
+    //highest blind level any table has reached, then moves players between `tables`
+    //so that no table has more than one player more than another, and breaks tables
+    //once the players left fit on fewer of them, down to the final table. Only tables
+    //between hands are touched, so this is called again after hands end until every
+    //table is balanced.
+    pub fn balance_tables(&mut self, tables: &mut Vec<PokerContract>) -> Result<()> {
+        if !self.started {
+            return Err(ContractError::new("The tournament has not started yet."));
+        }
+        self.place_players(tables);
+        if self.over {
+            return Ok(());
+        }
+        let level = tables.iter().map(|table| table.get_level()).max().unwrap_or(0);
+        for table in tables.iter_mut() {
+            table.raise_level(level);
+        }
+        let table_size = self.options.get_max_players();
+        loop {
+            let live: Vec<usize> = (0..tables.len())
+                .filter(|i| tables[*i].players_remaining() > 0)
+                .collect();
+            let remaining: u64 = live.iter().map(|i| tables[*i].players_remaining()).sum();
+            let needed = (remaining + table_size - 1) / table_size;
+            let smallest = match live.iter().min_by_key(|i| tables[**i].players_remaining()) {
+                Some(i) => *i,
+                None => return Ok(()),
+            };
+            let largest = match live.iter().max_by_key(|i| tables[**i].players_remaining()) {
+                Some(i) => *i,
+                None => return Ok(()),
+            };
+            if live.len() as u64 > needed {
+                //Break the smallest table, one player at a time
+                if !tables[smallest].between_hands() {
+                    return Ok(());
+                }
+                let target = live
+                    .iter()
+                    .filter(|i| **i != smallest && tables[**i].between_hands())
+                    .filter(|i| tables[**i].players_remaining() < table_size)
+                    .min_by_key(|i| tables[**i].players_remaining());
+                match target {
+                    Some(target) => move_player(tables, smallest, *target)?,
+                    None => return Ok(()),
+                }
+            } else if tables[largest].players_remaining()
+                > tables[smallest].players_remaining() + 1
+            {
+                if !tables[largest].between_hands() || !tables[smallest].between_hands() {
+                    return Ok(());
+                }
+                move_player(tables, largest, smallest)?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    //Pays out the prize a player won. Returns the amount paid.
+    pub fn claim_prize(&mut self, msg_sender: &Address) -> Result<u64> {
+        let result = self.results
+            .iter()
+            .position(|result| result.get_addr() == msg_sender.to_string());
+        match result {
+            Some(i) if !self.results[i].get_claimed() => {
+                self.results[i].set_claimed(true);
+                Ok(self.results[i].get_prize())
+            }
+            Some(_) => Err(ContractError::new("This prize has already been claimed.")),
+            None => Err(ContractError::new(
+                "This player has not finished the tournament.",
+            )),
+        }
+    }
+
+    //Adds the buy-in paid for a rebuy or add-on at one of the tournament's tables to
+    //the prize pool. The table itself checks that the purchase is allowed.
+    pub fn add_to_prize_pool(&mut self, deposit: u64) -> Result<()> {
+        if !self.started || self.over {
+            return Err(ContractError::new("The tournament is not in play."));
+        }
+        self.prize_pool += deposit;
+        Ok(())
+    }
+
+    pub fn get_game_ids(&self) -> &[u64] {
+        &self.game_ids
+    }
+
+    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+    // HELPER FUNCTIONS
+    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+
+    //Places the players each table has knocked out since the last call. Players still
+    //at a table finish ahead of them, and players knocked out in the same call are
+    //placed in the order their tables are listed. Once one player is left, he or she
+    //wins the rest of the prize pool and play stops.
+    fn place_players(&mut self, tables: &mut Vec<PokerContract>) {
+        let mut busted = Vec::new();
+        for table in tables.iter_mut() {
+            busted.extend(table.take_unplaced());
+        }
+        let remaining: u64 = tables.iter().map(|table| table.players_remaining()).sum();
+        let mut place = remaining + busted.len() as u64;
+        for addr in busted {
+            self.record_finish(addr, place);
+            place -= 1;
+        }
+
+        if remaining == 1 {
+            for table in tables.iter_mut() {
+                if let Some(winner) = table.seated_players().pop() {
+                    self.record_finish(winner, 1);
+                }
+                table.end_tournament();
+            }
+            self.over = true;
+        }
+    }
+
+    //Records where a player finished and the prize he or she won.
+    fn record_finish(&mut self, addr: String, place: u64) {
+        let result = finish(
+            self.options.get_tournament(),
+            self.prize_pool,
+            &self.results,
+            addr,
+            place,
+        );
+        self.results.push(result);
+    }
+}
+
+//Moves the player due to post the next big blind at table `from` to table `to`.
+fn move_player(tables: &mut Vec<PokerContract>, from: usize, to: usize) -> Result<()> {
+    let seat = tables[from].next_big_blind_seat();
+    let player = tables[from].unseat_player(seat)?;
+    tables[to].seat_player(player)
+}
+
+impl Contract<TournamentState> for Coordinator {
+    /// Get serializable contract state.
+    fn get_state(&self) -> TournamentState {
+        let mut state = TournamentState::new();
+        state.set_tournament_id(self.tournament_id);
+        state.set_options(self.options.clone());
+        state.set_entrants(self.entrants.clone());
+        state.set_prize_pool(self.prize_pool);
+        state.set_game_ids(self.game_ids.clone());
+        state.set_results(self.results.clone());
+        state.set_nonces(serialize_nonces(&self.nonces));
+        state.set_started(self.started);
+        state.set_over(self.over);
+
+        state
+    }
+
+    /// Create contract instance from serialized state.
+    fn from_state(state: &TournamentState) -> Coordinator {
+        Coordinator {
+            tournament_id: state.get_tournament_id(),
+            options: state.get_options().clone(),
+            entrants: state.get_entrants().to_vec(),
+            prize_pool: state.get_prize_pool(),
+            game_ids: state.get_game_ids().to_vec(),
+            results: state.get_results().to_vec(),
+            nonces: state
+                .get_nonces()
+                .iter()
+                .map(|n| (n.get_addr().to_string(), n.get_nonce()))
+                .collect(),
+            started: state.get_started(),
+            over: state.get_over(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::MockClock;
+    use poker_api::{BlindLevel, TournamentConfig};
+
+    fn addr(i: usize) -> Address {
+        Address::from(format!("player{}", i))
+    }
+
+    //Heads-up tables with a new blind level every minute, and rebuys during the first.
+    fn options() -> CreateGameRequest {
+        let mut config = TournamentConfig::new();
+        config.set_buy_in(100);
+        config.set_starting_stack(1000);
+        config.set_seconds_per_level(60);
+        config.set_rebuy_levels(1);
+        for blind in [10, 20, 40].iter() {
+            let mut level = BlindLevel::new();
+            level.set_blind(*blind);
+            config.mut_levels().push(level);
+        }
+        let mut options = CreateGameRequest::new();
+        options.set_max_players(2);
+        options.set_time_per_turn(30);
+        options.set_tournament(config);
+        options
+    }
+
+    //Starts a tournament at time 500 with four players on two tables.
+    fn start() -> (Coordinator, Vec<PokerContract>) {
+        let mut coordinator = Coordinator::new(1, &options()).unwrap();
+        for i in 0..4 {
+            coordinator.register(&addr(i), 100).unwrap();
+        }
+        let tables = coordinator.start(&addr(0), &[1, 2], 500).unwrap();
+        (coordinator, tables)
+    }
+
+    #[test]
+    fn tables_share_the_tournament_blind_clock() {
+        let (_, mut tables) = start();
+        tables[0].set_clock(Box::new(MockClock::new(560)));
+        tables[0].play_hand(&addr(0)).unwrap();
+        assert_eq!(tables[0].get_level(), 1);
+    }
+
+    #[test]
+    fn balancing_brings_every_table_to_the_same_level() {
+        let (mut coordinator, mut tables) = start();
+        tables[0].set_clock(Box::new(MockClock::new(560)));
+        tables[0].play_hand(&addr(0)).unwrap();
+        assert_eq!(tables[1].get_level(), 0);
+
+        coordinator.balance_tables(&mut tables).unwrap();
+        assert_eq!(tables[1].get_level(), 1);
+    }
+
+    #[test]
+    fn rebuys_at_the_tables_go_into_the_prize_pool() {
+        let mut coordinator = Coordinator::new(1, &options()).unwrap();
+        coordinator.register(&addr(0), 100).unwrap();
+        coordinator.register(&addr(1), 100).unwrap();
+        assert!(coordinator.add_to_prize_pool(100).is_err());
+
+        coordinator.start(&addr(0), &[1], 500).unwrap();
+        coordinator.add_to_prize_pool(100).unwrap();
+        assert_eq!(coordinator.prize_pool, 300);
+    }
+}
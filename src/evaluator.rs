@@ -0,0 +1,324 @@
+//Showdown hand evaluation built on top of `rs_poker`.
+use rs_poker::core::{Card, Hand, Rank, Rankable, Suit};
+
+use poker_api::HandResult;
+use serde_cbor;
+
+//The best five-card hand a player made at showdown.
+#[derive(Clone)]
+pub struct ShowdownHand {
+    pub addr: String,
+    pub rank: Rank,
+    pub cards: Vec<Card>,
+}
+
+impl ShowdownHand {
+    pub fn to_state(&self) -> HandResult {
+        let mut state = HandResult::new();
+        state.set_addr(self.addr.clone());
+        state.set_category(category(&self.rank).to_string());
+        state.set_kickers(kickers(&self.rank, &self.cards));
+        state.set_cards(serde_cbor::to_vec(&self.cards).expect("Unable to serialize cards."));
+        state
+    }
+
+    pub fn from_state(state: &HandResult) -> ShowdownHand {
+        let cards: Vec<Card> =
+            serde_cbor::from_slice(state.get_cards()).expect("Unable to deserialize cards");
+        ShowdownHand {
+            addr: state.get_addr().to_string(),
+            rank: Hand::new_with_cards(cards.clone()).rank_five(),
+            cards,
+        }
+    }
+}
+
+//Finds the best five-card hand that can be made from `cards`, returning its rank
+//together with the five cards that make it. Returns `None` if there are fewer
+//than five cards.
+pub fn best_five(cards: &[Card]) -> Option<(Rank, Vec<Card>)> {
+    let mut best: Option<(Rank, Vec<Card>)> = None;
+    for combination in combinations(cards.len(), 5) {
+        let five: Vec<Card> = combination.iter().map(|i| cards[*i]).collect();
+        let rank = Hand::new_with_cards(five.clone()).rank_five();
+        let better = match best {
+            Some((ref best_rank, _)) => rank > *best_rank,
+            None => true,
+        };
+        if better {
+            best = Some((rank, five));
+        }
+    }
+    best
+}
+
+//Finds the best Omaha hand: exactly two of the `hole` cards with exactly three of the
+//`board` cards. Returns `None` if there are too few cards to make one.
+pub fn best_omaha(hole: &[Card], board: &[Card]) -> Option<(Rank, Vec<Card>)> {
+    let mut best: Option<(Rank, Vec<Card>)> = None;
+    for from_hole in combinations(hole.len(), 2) {
+        for from_board in combinations(board.len(), 3) {
+            let five: Vec<Card> = from_hole
+                .iter()
+                .map(|i| hole[*i])
+                .chain(from_board.iter().map(|i| board[*i]))
+                .collect();
+            let rank = Hand::new_with_cards(five.clone()).rank_five();
+            let better = match best {
+                Some((ref best_rank, _)) => rank > *best_rank,
+                None => true,
+            };
+            if better {
+                best = Some((rank, five));
+            }
+        }
+    }
+    best
+}
+
+//Strength of the up cards a stud player is showing, used to decide who acts first.
+//Larger groups of equal cards rank first, then higher values. Straights and flushes
+//do not count, as in the showing hands of stud.
+pub fn showing_strength(cards: &[Card]) -> Vec<u32> {
+    let values: Vec<u32> = cards.iter().map(|card| card.value as u32).collect();
+    let count = |value: u32| values.iter().filter(|v| **v == value).count() as u32;
+    let mut ordered = values.clone();
+    ordered.sort_by(|a, b| count(*b).cmp(&count(*a)).then(b.cmp(a)));
+    let mut strength: Vec<u32> = ordered.iter().map(|value| count(*value)).collect();
+    strength.extend(ordered);
+    strength
+}
+
+//Order of a card when looking for the lowest up card to bring in the betting: by
+//value with the ace high, then by suit from clubs up to spades.
+pub fn bring_in_order(card: &Card) -> u32 {
+    let suit = match card.suit {
+        Suit::Club => 0,
+        Suit::Diamond => 1,
+        Suit::Heart => 2,
+        Suit::Spade => 3,
+    };
+    4 * card.value as u32 + suit
+}
+
+//Every way of choosing `k` indices out of `0..n`, in lexicographic order.
+pub fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut result = Vec::new();
+    if k > n {
+        return result;
+    }
+    let mut indices: Vec<usize> = (0..k).collect();
+    loop {
+        result.push(indices.clone());
+        //Find the rightmost index that can still be moved up
+        let mut i = k;
+        while i > 0 && indices[i - 1] == i - 1 + n - k {
+            i -= 1;
+        }
+        if i == 0 {
+            return result;
+        }
+        indices[i - 1] += 1;
+        for j in i..k {
+            indices[j] = indices[j - 1] + 1;
+        }
+    }
+}
+
+//Name of the hand category, e.g. "FullHouse".
+pub fn category(rank: &Rank) -> &'static str {
+    match *rank {
+        Rank::HighCard(_) => "HighCard",
+        Rank::OnePair(_) => "OnePair",
+        Rank::TwoPair(_) => "TwoPair",
+        Rank::ThreeOfAKind(_) => "ThreeOfAKind",
+        Rank::Straight(_) => "Straight",
+        Rank::Flush(_) => "Flush",
+        Rank::FullHouse(_) => "FullHouse",
+        Rank::FourOfAKind(_) => "FourOfAKind",
+        Rank::StraightFlush(_) => "StraightFlush",
+    }
+}
+
+//Card values of a five-card hand in the order they are compared: larger groups
+//first, then higher values. Values run from 0 (two) to 12 (ace).
+pub fn kickers(rank: &Rank, cards: &[Card]) -> Vec<u32> {
+    let values: Vec<u32> = cards.iter().map(|card| card.value as u32).collect();
+    let count = |value: u32, values: &Vec<u32>| values.iter().filter(|v| **v == value).count();
+    let mut ordered = values.clone();
+    ordered.sort_by(|a, b| {
+        count(*b, &values)
+            .cmp(&count(*a, &values))
+            .then(b.cmp(a))
+    });
+    //In a five-high straight the ace plays low
+    match *rank {
+        Rank::Straight(_) | Rank::StraightFlush(_) => {
+            if ordered.contains(&12) && ordered.contains(&0) {
+                ordered.remove(0);
+                ordered.push(12);
+            }
+        }
+        _ => (),
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs_poker::core::Value;
+
+    fn card(value: Value, suit: Suit) -> Card {
+        Card { value, suit }
+    }
+
+    #[test]
+    fn combinations_are_in_lexicographic_order() {
+        assert_eq!(
+            combinations(4, 2),
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3],
+            ]
+        );
+        assert_eq!(combinations(5, 5), vec![vec![0, 1, 2, 3, 4]]);
+        assert!(combinations(2, 3).is_empty());
+    }
+
+    #[test]
+    fn best_five_of_seven_uses_the_board() {
+        let cards = vec![
+            card(Value::Ace, Suit::Spade),
+            card(Value::Ace, Suit::Heart),
+            card(Value::Ace, Suit::Club),
+            card(Value::King, Suit::Diamond),
+            card(Value::King, Suit::Heart),
+            card(Value::Two, Suit::Spade),
+            card(Value::Three, Suit::Club),
+        ];
+        let (rank, five) = best_five(&cards).unwrap();
+        assert_eq!(category(&rank), "FullHouse");
+        assert_eq!(kickers(&rank, &five), vec![12, 12, 12, 11, 11]);
+    }
+
+    #[test]
+    fn best_five_needs_five_cards() {
+        let cards = vec![
+            card(Value::Ace, Suit::Spade),
+            card(Value::King, Suit::Spade),
+        ];
+        assert!(best_five(&cards).is_none());
+    }
+
+    #[test]
+    fn kickers_put_larger_groups_first() {
+        let cards = vec![
+            card(Value::Four, Suit::Spade),
+            card(Value::King, Suit::Heart),
+            card(Value::Four, Suit::Club),
+            card(Value::Nine, Suit::Diamond),
+            card(Value::Ace, Suit::Heart),
+        ];
+        let (rank, five) = best_five(&cards).unwrap();
+        assert_eq!(category(&rank), "OnePair");
+        assert_eq!(kickers(&rank, &five), vec![2, 2, 12, 11, 7]);
+    }
+
+    #[test]
+    fn ace_plays_low_in_a_five_high_straight() {
+        let cards = vec![
+            card(Value::Ace, Suit::Spade),
+            card(Value::Two, Suit::Heart),
+            card(Value::Three, Suit::Club),
+            card(Value::Four, Suit::Diamond),
+            card(Value::Five, Suit::Heart),
+        ];
+        let (rank, five) = best_five(&cards).unwrap();
+        assert_eq!(category(&rank), "Straight");
+        assert_eq!(kickers(&rank, &five), vec![3, 2, 1, 0, 12]);
+    }
+
+    #[test]
+    fn omaha_uses_exactly_two_hole_cards() {
+        //With one hole card this board would make a royal flush
+        let hole = vec![
+            card(Value::Ace, Suit::Spade),
+            card(Value::Two, Suit::Diamond),
+            card(Value::Three, Suit::Diamond),
+            card(Value::Four, Suit::Diamond),
+        ];
+        let board = vec![
+            card(Value::King, Suit::Spade),
+            card(Value::Queen, Suit::Spade),
+            card(Value::Jack, Suit::Spade),
+            card(Value::Ten, Suit::Spade),
+            card(Value::Nine, Suit::Heart),
+        ];
+        let (rank, five) = best_omaha(&hole, &board).unwrap();
+        assert_eq!(category(&rank), "HighCard");
+        assert_eq!(five.iter().filter(|card| hole.contains(card)).count(), 2);
+        assert!(best_omaha(&hole[..1], &board).is_none());
+    }
+
+    #[test]
+    fn omaha_flush_needs_two_suited_hole_cards() {
+        let hole = vec![
+            card(Value::Ace, Suit::Spade),
+            card(Value::King, Suit::Spade),
+            card(Value::Queen, Suit::Diamond),
+            card(Value::Jack, Suit::Diamond),
+        ];
+        let board = vec![
+            card(Value::Ten, Suit::Spade),
+            card(Value::Nine, Suit::Spade),
+            card(Value::Two, Suit::Spade),
+            card(Value::Three, Suit::Heart),
+            card(Value::Four, Suit::Club),
+        ];
+        let (rank, _) = best_omaha(&hole, &board).unwrap();
+        assert_eq!(category(&rank), "Flush");
+    }
+
+    #[test]
+    fn showing_strength_ranks_groups_before_values() {
+        let pair = showing_strength(&[
+            card(Value::Two, Suit::Heart),
+            card(Value::Two, Suit::Spade),
+        ]);
+        let ace_high = showing_strength(&[
+            card(Value::Ace, Suit::Heart),
+            card(Value::King, Suit::Spade),
+        ]);
+        assert!(pair > ace_high);
+
+        let trips = showing_strength(&[
+            card(Value::Five, Suit::Heart),
+            card(Value::Five, Suit::Spade),
+            card(Value::Five, Suit::Club),
+            card(Value::Two, Suit::Club),
+        ]);
+        let two_pair = showing_strength(&[
+            card(Value::King, Suit::Heart),
+            card(Value::King, Suit::Spade),
+            card(Value::Queen, Suit::Club),
+            card(Value::Queen, Suit::Diamond),
+        ]);
+        assert!(trips > two_pair);
+    }
+
+    #[test]
+    fn bring_in_goes_by_value_then_suit() {
+        let two_of_clubs = bring_in_order(&card(Value::Two, Suit::Club));
+        let two_of_spades = bring_in_order(&card(Value::Two, Suit::Spade));
+        let three_of_clubs = bring_in_order(&card(Value::Three, Suit::Club));
+        let ace_of_clubs = bring_in_order(&card(Value::Ace, Suit::Club));
+        assert!(two_of_clubs < two_of_spades);
+        assert!(two_of_spades < three_of_clubs);
+        assert!(three_of_clubs < ace_of_clubs);
+    }
+}
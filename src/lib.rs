@@ -2,6 +2,7 @@
 #![feature(use_extern_macros)]
 extern crate protobuf;
 
+extern crate ed25519_dalek;
 extern crate ekiden_core_common;
 extern crate ekiden_core_trusted;
 
@@ -10,128 +11,424 @@ extern crate poker_api;
 /*extern crate rand;*/
 extern crate rs_poker;
 extern crate serde_cbor;
+extern crate sha2;
 
+#[macro_use]
+mod auth;
+mod clock;
+mod coordinator;
+mod evaluator;
 mod poker_contract;
+mod tournament;
 
 use ekiden_core_common::Result;
 use ekiden_core_common::contract::{with_contract_state, Address, Contract};
 use ekiden_core_trusted::db::Db;
 use ekiden_core_trusted::rpc::create_enclave_rpc;
+use protobuf::Message;
+
+use clock::{Clock, EnclaveClock};
+use coordinator::Coordinator;
+use poker_contract::{Action, PokerContract, QueuedAction};
 
 with_api! {
     create_enclave_rpc!(api);
 }
 
-fn create(request: &CreateGameRequest) -> Result<CreateGameResponse> {
-    let contract = PokerContract::new(
-        request.get_blind(),
-        request.get_max_players(),
-        request.get_time_per_turn(),
-    );
+//Db key under which a table's state is stored.
+fn game_key(game_id: u64) -> String {
+    format!("game_{}", game_id)
+}
 
-    let response = CreateGameResponse::new();
+//Db key under which a multi-table tournament's state is stored.
+fn tournament_key(tournament_id: u64) -> String {
+    format!("tournament_{}", tournament_id)
+}
+
+//Index of every table created in this enclave. Empty until the first table is created.
+fn get_game_index() -> GameIndex {
+    match Db::instance().get("games") {
+        Ok(index) => index,
+        Err(_) => GameIndex::new(),
+    }
+}
+
+//Loads table `game_id`, checks the request is meant for it, runs `op` for the
+//authenticated sender and stores the table again, as long as `op` succeeds and every
+//chip is still accounted for.
+fn update_table<T, F>(
+    msg_sender: &Address,
+    game_id: u64,
+    hand_number: u64,
+    nonce: u64,
+    op: F,
+) -> Result<T>
+where
+    F: FnOnce(&mut PokerContract) -> Result<T>,
+{
+    let state = Db::instance().get(&game_key(game_id))?;
+    let mut contract = PokerContract::from_state(&state);
+    contract.check_request(msg_sender, game_id, hand_number, nonce)?;
+    let result = op(&mut contract)?;
+    contract.check_chips()?;
+    Db::instance().set(&game_key(game_id), contract.get_state())?;
+
+    Ok(result)
+}
+
+//Authenticates a table request and runs `$op` with the table as `$contract` and the
+//sender as `$sender`. See `update_table`.
+macro_rules! with_table {
+    ($request:expr, |$contract:ident, $sender:ident| $op:expr) => {{
+        let $sender = authenticate!($request);
+        update_table(
+            &$sender,
+            $request.get_game_id(),
+            $request.get_hand_number(),
+            $request.get_nonce(),
+            |$contract: &mut PokerContract| $op,
+        )
+    }};
+}
+
+fn create(request: &CreateGameRequest) -> Result<CreateGameResponse> {
+    //Game ids are handed out sequentially so that no two tables share a key
+    let mut index = get_game_index();
+    let game_id = index.get_next_game_id() + 1;
+    let contract = PokerContract::new(game_id, request)?;
+    index.set_next_game_id(game_id);
+    index.mut_game_ids().push(game_id);
+
+    let mut response = CreateGameResponse::new();
     response.set_success(true);
+    response.set_game_id(game_id);
 
-    Db::instance().set("state", contract.get_state())?;
+    Db::instance().set(&game_key(game_id), contract.get_state())?;
+    Db::instance().set("games", index)?;
 
     Ok(response)
 }
 
-fn join(request: &JoinGameRequest) -> Result<JoinGameResponse> {
-    let state = Db::instance().get("state")?;
-    let mut playing;
-    let state = with_contract_state(&state, |contract: &mut PokerContract| {
-        playing = contract.join_game(
-            &Address::from(request.get_sender().to_string()),
-            request.get_deposit(),
-            request.get_seed(),
-        )?;
-
-        Ok(());
-    })?;
+//Lists the tables new players can join.
+fn list_games(_request: &ListGamesRequest) -> Result<ListGamesResponse> {
+    let mut response = ListGamesResponse::new();
+    for game_id in get_game_index().get_game_ids().iter() {
+        let state = Db::instance().get(&game_key(*game_id))?;
+        let contract = PokerContract::from_state(&state);
+        if contract.is_open() {
+            response.mut_games().push(contract.get_summary());
+        }
+    }
 
-    let response = JoinGameResponse::new();
-    response.set_success(true);
-    response.set_playing(playing);
+    Ok(response)
+}
 
-    Db::instance().set("state", state)?;
+fn join(request: &JoinGameRequest) -> Result<JoinGameResponse> {
+    let playing = with_table!(request, |contract, msg_sender| contract.join_game(
+        &msg_sender,
+        request.get_deposit(),
+        request.get_wait_for_big_blind(),
+    ))?;
+
+    let mut response = JoinGameResponse::new();
+    response.set_joined(true);
+    response.set_playing(playing);
 
     Ok(response)
 }
 
 fn play(request: &PlayHandRequest) -> Result<PlayHandResponse> {
-    let state = Db::instance().get("state")?;
-    let state = with_contract_state(&state, |contract: &mut PokerContract| {
-        contract.play_hand(&Address::from(request.get_sender().tos_string()))?;
+    with_table!(request, |contract, msg_sender| contract.play_hand(&msg_sender))?;
 
-        Ok(())
-    })?;
+    let mut response = PlayHandResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
 
-    let response = PlayHandResponse::new();
+fn commit_seed(request: &CommitSeedRequest) -> Result<CommitSeedResponse> {
+    with_table!(request, |contract, msg_sender| contract
+        .commit_seed(&msg_sender, request.get_commitment()))?;
+
+    let mut response = CommitSeedResponse::new();
     response.set_success(true);
 
-    Db::instance().set("state", state)?;
+    Ok(response)
+}
+
+fn reveal_seed(request: &RevealSeedRequest) -> Result<RevealSeedResponse> {
+    with_table!(request, |contract, msg_sender| contract
+        .reveal_seed(&msg_sender, request.get_seed()))?;
+
+    let mut response = RevealSeedResponse::new();
+    response.set_success(true);
 
     Ok(response)
 }
 
 fn take_action(request: &TakeActionRequest) -> Result<TakeActionResponse> {
-    let state = Db::instance().get("state")?;
-    let state = with_contract_state(&state, |contract: &mut PokerContract| {
-        let action = match request.get_action().to_string() {
-            "Check" => poker_contract::Action::Check,
-            "Match" => poker_contract::Action::Match,
-            "Raise" => poker_contract::Action::Raise,
-            "Fold" => poker_contract::Action::Fold,
-            () => poker_contract::Action::None,
-        };
-        contract.take_action(
-            &Address::from(request.get_sender().to_string()),
-            action,
-            request.get_value(),
-        )?;
-
-        Ok(());
-    })?;
+    with_table!(request, |contract, msg_sender| contract.take_action(
+        &msg_sender,
+        Action::from_proto(request.get_action()),
+        request.get_amount(),
+    ))?;
 
-    let response = TakeActionResponse::new();
+    let mut response = TakeActionResponse::new();
     response.set_success(true);
 
-    Db::instance().set("state", state)?;
+    Ok(response)
+}
+
+fn queue_action(request: &QueueActionRequest) -> Result<QueueActionResponse> {
+    with_table!(request, |contract, msg_sender| contract
+        .queue_action(&msg_sender, QueuedAction::from_proto(request.get_action())))?;
+
+    let mut response = QueueActionResponse::new();
+    response.set_success(true);
 
     Ok(response)
 }
 
-fn leave(request: &WithdrawRequest) -> Result<WithdrawResponse> {
-    let state = Db::instance().get("state")?;
-    let mut balance = 0;
-    let state = with_contract_state(&state, |contract: &mut PokerContract| {
-        balance = contract.withdraw(&Address::from(request.get_sender().to_string()))?;
+fn sit_out(request: &SitOutRequest) -> Result<SitOutResponse> {
+    with_table!(request, |contract, msg_sender| contract.sit_out(&msg_sender))?;
 
-        Ok(())
+    let mut response = SitOutResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
+
+fn sit_in(request: &SitInRequest) -> Result<SitInResponse> {
+    with_table!(request, |contract, msg_sender| contract.sit_in(&msg_sender))?;
+
+    let mut response = SitInResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
+
+fn top_up(request: &TopUpRequest) -> Result<TopUpResponse> {
+    with_table!(request, |contract, msg_sender| contract
+        .top_up(&msg_sender, request.get_amount()))?;
+
+    let mut response = TopUpResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
+
+//Pays a rebuy or add-on bought at a table of multi-table tournament `tournament_id`
+//into the tournament's prize pool. Tables played on their own keep their own.
+fn pay_into_prize_pool(tournament_id: u64, deposit: u64) -> Result<()> {
+    if tournament_id == 0 {
+        return Ok(());
+    }
+    let state = Db::instance().get(&tournament_key(tournament_id))?;
+    let state = with_contract_state(&state, |coordinator: &mut Coordinator| {
+        coordinator.add_to_prize_pool(deposit)
+    })?;
+    Db::instance().set(&tournament_key(tournament_id), state)?;
+
+    Ok(())
+}
+
+fn rebuy(request: &RebuyRequest) -> Result<RebuyResponse> {
+    let tournament_id = with_table!(request, |contract, msg_sender| {
+        contract.rebuy(&msg_sender, request.get_deposit())?;
+        Ok(contract.get_coordinator())
+    })?;
+    pay_into_prize_pool(tournament_id, request.get_deposit())?;
+
+    let mut response = RebuyResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
+
+fn add_on(request: &AddOnRequest) -> Result<AddOnResponse> {
+    let tournament_id = with_table!(request, |contract, msg_sender| {
+        contract.add_on(&msg_sender, request.get_deposit())?;
+        Ok(contract.get_coordinator())
     })?;
+    pay_into_prize_pool(tournament_id, request.get_deposit())?;
 
-    let response = WithdrawResponse::new();
+    let mut response = AddOnResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
+
+fn leave(request: &WithdrawRequest) -> Result<WithdrawResponse> {
+    let balance = with_table!(request, |contract, msg_sender| contract.withdraw(&msg_sender))?;
+
+    let mut response = WithdrawResponse::new();
     response.set_success(true);
     response.set_balance(balance);
 
-    Db::instance().set("state", state)?;
+    Ok(response)
+}
+
+fn withdraw_rake(request: &WithdrawRakeRequest) -> Result<WithdrawRakeResponse> {
+    let amount = with_table!(request, |contract, msg_sender| contract
+        .withdraw_rake(&msg_sender))?;
+
+    let mut response = WithdrawRakeResponse::new();
+    response.set_success(true);
+    response.set_amount(amount);
 
     Ok(response)
 }
 
-/*fn get_player_information(request: &PlayerStateRequest) -> Result<PlayerState, ContractError> {
-    let state = Db::instance().get("state")?;
-    let contract = PokerContract::from_state(state);
-    let player_state = contract.get_player_state(&Address::from(request.get_sender().to_string()));
+fn claim_timeout(request: &ClaimTimeoutRequest) -> Result<ClaimTimeoutResponse> {
+    with_table!(request, |contract, msg_sender| contract.claim_timeout(&msg_sender))?;
+
+    let mut response = ClaimTimeoutResponse::new();
+    response.set_success(true);
+
+    Ok(response)
+}
+
+fn get_player_state(request: &PlayerStateRequest) -> Result<PlayerState> {
+    let msg_sender = authenticate!(request);
+    let state = Db::instance().get(&game_key(request.get_game_id()))?;
+    let contract = PokerContract::from_state(&state);
+    let player_state = contract.get_player_state(
+        &msg_sender,
+        request.get_hand_number(),
+        request.get_timestamp(),
+    )?;
 
     Ok(player_state)
 }
 
-fn get_game_information(request: &PublicStateRequest) -> Result<PublicState, ContractError> {
-    let state = Db::instance().get("state")?;
-    let contract = PokerContract::from_state(state);
-    let public_game_state = contract.get_public_state();
+fn get_public_state(request: &PublicStateRequest) -> Result<PublicState> {
+    let state = Db::instance().get(&game_key(request.get_game_id()))?;
+    let contract = PokerContract::from_state(&state);
+    //Nothing private is returned, so the sender is only used to work out legal actions
+    let public_game_state =
+        contract.get_public_state(&Address::from(request.get_sender().to_string()))?;
 
     Ok(public_game_state)
-}*/
+}
+
+fn create_tournament(request: &CreateTournamentRequest) -> Result<CreateTournamentResponse> {
+    let mut index = get_game_index();
+    let tournament_id = index.get_next_tournament_id() + 1;
+    let coordinator = Coordinator::new(tournament_id, request.get_options())?;
+    index.set_next_tournament_id(tournament_id);
+
+    let mut response = CreateTournamentResponse::new();
+    response.set_success(true);
+    response.set_tournament_id(tournament_id);
+
+    Db::instance().set(&tournament_key(tournament_id), coordinator.get_state())?;
+    Db::instance().set("games", index)?;
+
+    Ok(response)
+}
+
+fn register(request: &RegisterRequest) -> Result<RegisterResponse> {
+    let msg_sender = authenticate!(request);
+    let state = Db::instance().get(&tournament_key(request.get_tournament_id()))?;
+    let state = with_contract_state(&state, |coordinator: &mut Coordinator| {
+        coordinator.check_request(&msg_sender, request.get_tournament_id(), request.get_nonce())?;
+        coordinator.register(&msg_sender, request.get_deposit())?;
+
+        Ok(())
+    })?;
+
+    let mut response = RegisterResponse::new();
+    response.set_success(true);
+
+    Db::instance().set(&tournament_key(request.get_tournament_id()), state)?;
+
+    Ok(response)
+}
+
+fn start_tournament(request: &StartTournamentRequest) -> Result<StartTournamentResponse> {
+    let msg_sender = authenticate!(request);
+    let state = Db::instance().get(&tournament_key(request.get_tournament_id()))?;
+    let mut coordinator = Coordinator::from_state(&state);
+    coordinator.check_request(&msg_sender, request.get_tournament_id(), request.get_nonce())?;
+
+    //The tables get game ids from the same index as other tables, so no two share a key
+    let mut index = get_game_index();
+    let mut game_ids = Vec::new();
+    for _ in 0..coordinator.tables_needed() {
+        let game_id = index.get_next_game_id() + 1;
+        index.set_next_game_id(game_id);
+        index.mut_game_ids().push(game_id);
+        game_ids.push(game_id);
+    }
+    let tables = coordinator.start(&msg_sender, &game_ids, EnclaveClock.now())?;
+
+    let mut response = StartTournamentResponse::new();
+    response.set_success(true);
+    response.set_game_ids(game_ids.clone());
+
+    for (game_id, table) in game_ids.iter().zip(tables.iter()) {
+        Db::instance().set(&game_key(*game_id), table.get_state())?;
+    }
+    Db::instance().set("games", index)?;
+    Db::instance().set(
+        &tournament_key(request.get_tournament_id()),
+        coordinator.get_state(),
+    )?;
+
+    Ok(response)
+}
+
+//Anyone can ask for the tables to be balanced, as it only ever moves players the
+//tournament rules call for.
+fn balance_tables(request: &BalanceTablesRequest) -> Result<BalanceTablesResponse> {
+    let state = Db::instance().get(&tournament_key(request.get_tournament_id()))?;
+    let mut coordinator = Coordinator::from_state(&state);
+    let mut tables = Vec::new();
+    for game_id in coordinator.get_game_ids().iter() {
+        let state = Db::instance().get(&game_key(*game_id))?;
+        tables.push(PokerContract::from_state(&state));
+    }
+    coordinator.balance_tables(&mut tables)?;
+    for table in tables.iter() {
+        table.check_chips()?;
+    }
+
+    let mut response = BalanceTablesResponse::new();
+    response.set_success(true);
+
+    for (game_id, table) in coordinator.get_game_ids().iter().zip(tables.iter()) {
+        Db::instance().set(&game_key(*game_id), table.get_state())?;
+    }
+    Db::instance().set(
+        &tournament_key(request.get_tournament_id()),
+        coordinator.get_state(),
+    )?;
+
+    Ok(response)
+}
+
+fn claim_prize(request: &ClaimPrizeRequest) -> Result<ClaimPrizeResponse> {
+    let msg_sender = authenticate!(request);
+    let state = Db::instance().get(&tournament_key(request.get_tournament_id()))?;
+    let mut amount = 0;
+    let state = with_contract_state(&state, |coordinator: &mut Coordinator| {
+        coordinator.check_request(&msg_sender, request.get_tournament_id(), request.get_nonce())?;
+        amount = coordinator.claim_prize(&msg_sender)?;
+
+        Ok(())
+    })?;
+
+    let mut response = ClaimPrizeResponse::new();
+    response.set_success(true);
+    response.set_amount(amount);
+
+    Db::instance().set(&tournament_key(request.get_tournament_id()), state)?;
+
+    Ok(response)
+}
+
+fn get_tournament_state(request: &TournamentStateRequest) -> Result<TournamentState> {
+    let state = Db::instance().get(&tournament_key(request.get_tournament_id()))?;
+
+    Ok(state)
+}
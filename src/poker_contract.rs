@@ -1,31 +1,113 @@
-//An implementation of Texas Hold'em compatible with Ekiden
+//An implementation of Texas Hold'em, Omaha and seven-card stud compatible with Ekiden
 #![no_std]
-use ekiden_core_common::{Address, Contract};
+use ekiden_core_common::{Address, Contract, ContractError, Result};
+use ekiden_core_trusted::random::get_random_bytes;
 
-use poker_api::{PlayerState, PokerState, PublicState};
-use rs_poker::core::{Card, Deck, Hand, Rankable};
-use rand::*;
+use clock::{Clock, EnclaveClock};
+use evaluator::{best_five, best_omaha, bring_in_order, showing_strength, ShowdownHand};
+use poker_api::{
+    commit_seed, derive_seed, shuffle_deck, ActionType, BettingStructure, Contribution,
+    CreateGameRequest, DealRound, Finish, GameSummary, GameVariant, HandHistory, HandResult,
+    LegalAction, PlayerState, PokerState, PotState, PublicState, QueuedActionType, SeedReveal,
+    ShownHand, TournamentConfig,
+};
+use rs_poker::core::Card;
 use serde_cbor;
-use core::slice::Iter;
+use sha2::{Digest, Sha256};
+use tournament::{self, current_level, finish, serialize_nonces};
+use core::cmp::{max, min, Ordering};
+use core::mem;
 use std::collections::HashMap;
 
-pub struct PokerContract<'a> {
+pub struct PokerContract {
     game_id: u64,
+    //The big blind
     blind: u64,
+    small_blind_amount: u64,
+    ante: u64,
+    //Whether the player in the big blind posts the ante for the whole table
+    big_blind_ante: bool,
+    //Whether the player after the big blind posts a live straddle of two big blinds
+    straddle: bool,
     max_players: u64,
     time_per_turn: u64,
-    players: Vec<Player>,
+    variant: GameVariant,
+    betting_structure: BettingStructure,
+    //Number of bets and raises allowed per street in fixed-limit games
+    raise_cap: u64,
+    //Share of each pot kept by the house, in hundredths of a percent
+    rake_basis_points: u64,
+    //Most the house takes from a single hand, or 0 for no cap
+    rake_cap: u64,
+    //Whether hands that end before the flop are not raked
+    no_flop_no_drop: bool,
+    //Address allowed to withdraw the rake
+    operator: String,
+    //Limits on what a cash player can sit down with or top up to, or 0 for no limit
+    min_buy_in: u64,
+    max_buy_in: u64,
+    //Rake collected and not yet withdrawn by the operator
+    house_balance: u64,
+    //Buy-in, starting stack, blind schedule and payouts of a tournament table. Cash
+    //tables have no blind levels.
+    tournament: TournamentConfig,
+    //Current blind level of the tournament
+    level: u64,
+    //Time the first hand of the tournament started
+    started_at: u64,
+    //Buy-ins collected, paid out to the players who finish in the money
+    prize_pool: u64,
+    entrants: u64,
+    //Finishing places, in the order players were knocked out
+    results: Vec<Finish>,
+    tournament_over: bool,
+    //Multi-table tournament the table belongs to, or 0 for a table played on its own.
+    //Its players are seated, moved, placed and paid by the tournament coordinator.
+    coordinator: u64,
+    //One entry per seat, numbered from 0 to `max_players - 1`. Empty seats hold a
+    //placeholder player with the `Empty` status.
+    seats: Vec<Player>,
+    //Players waiting for a seat at a full table
     on_deck: Vec<Player>,
     index: HashMap<String, i32>,
     cards: Vec<Card>,
-    deck: &'a Iter<'a, Card>,
-    pot: u64,
+    deck: Vec<Card>,
+    pots: Vec<Pot>,
+    //Live chips each player has put into the pots this hand
+    contributions: HashMap<String, u64>,
+    //Dead blinds and antes posted this hand. They go to the main pot but do not count
+    //towards the side pot levels or the amount a player must cover to be eligible.
+    dead_money: HashMap<String, u64>,
+    hands: Vec<ShowdownHand>,
+    total_chips: u64,
+    //Last nonce used by each player. Kept after a player leaves so that his or her
+    //old requests cannot be replayed.
+    nonces: HashMap<String, u64>,
     min_bet: u64,
+    //Size of the last full bet or raise on this street
+    last_raise: u64,
+    //Number of bets and raises made on this street. The big blind counts as the
+    //first bet pre-flop.
+    raises: u64,
+    //Players who have acted since the last full bet or raise on this street. An
+    //all-in for less than a full raise does not reopen the betting for them.
+    acted: Vec<String>,
     dealer: i32,
+    //Seats of the blinds in the last hand dealt. The small blind may be a seat that was
+    //not dealt in, in which case no small blind was posted.
+    small_blind: i32,
+    big_blind: i32,
     next_player: i32,
     last_player: i32,
+    turn_deadline: u64,
     stage: GameStage,
+    hand_number: u64,
     seed: [u8; 32],
+    //Shuffle inputs and deck order of the hand in progress, kept secret until it ends
+    pending_history: HandHistory,
+    //Audit record of the last completed hand
+    history: HandHistory,
+    clock: Box<Clock>,
 }
 
 //TODO: how to index players and get the right one
@@ -34,58 +116,168 @@ pub struct PokerContract<'a> {
 //TODO: check shuffle flow
 //TODO: serialization stuff
 
-impl<'a> PokerContract<'a> {
+impl PokerContract {
     //Creates a new instance of a poker game with all values set to default
-    //save for provided parameters
-    pub fn new(blind: u64, max_players: u64, time_per_turn: u64) -> Result<PokerContract<'a>> {
-        if max_players > 22 || blind == 0 || time_per_turn == 0 {
+    //save for the table options in `options`
+    pub fn new(game_id: u64, options: &CreateGameRequest) -> Result<PokerContract> {
+        let tournament = options.get_tournament();
+        //A tournament starts at the first level of its blind schedule
+        let (blind, small_blind_amount, ante) = if tournament.get_levels().is_empty() {
+            (
+                options.get_blind(),
+                options.get_small_blind_amount(),
+                options.get_ante(),
+            )
+        } else {
+            tournament::validate(tournament)?;
+            if options.get_rake_basis_points() > 0 {
+                return Err(ContractError::new("Tournament pots are not raked."));
+            }
+            let level = &tournament.get_levels()[0];
+            (level.get_blind(), level.get_small_blind_amount(), level.get_ante())
+        };
+        let max_players = options.get_max_players();
+        let time_per_turn = options.get_time_per_turn();
+        if max_players < 2 || max_players > 22 || blind == 0 || time_per_turn == 0 {
             return Err(ContractError::new("Invalid game paramaters."));
         }
+        //Omaha is always played pot-limit and stud fixed-limit. A deck only has enough
+        //cards for 11 Omaha players with four hole cards each, or 7 stud players with
+        //seven cards each.
+        let variant = options.get_variant();
+        let betting_structure = match variant {
+            GameVariant::TEXAS_HOLDEM => options.get_betting_structure(),
+            GameVariant::OMAHA => {
+                if max_players > 11 {
+                    return Err(ContractError::new(
+                        "Omaha tables seat at most 11 players.",
+                    ));
+                }
+                BettingStructure::POT_LIMIT
+            }
+            GameVariant::SEVEN_CARD_STUD => {
+                if max_players > 7 {
+                    return Err(ContractError::new("Stud tables seat at most 7 players."));
+                }
+                if options.get_straddle() || options.get_big_blind_ante() {
+                    return Err(ContractError::new("Stud is played without blinds."));
+                }
+                BettingStructure::FIXED_LIMIT
+            }
+        };
+        //The small blind is half the big blind unless told otherwise
+        let small_blind_amount = match small_blind_amount {
+            0 => blind / 2,
+            amount => amount,
+        };
+        if small_blind_amount > blind {
+            return Err(ContractError::new(
+                "The small blind cannot be larger than the big blind.",
+            ));
+        }
+        //Fixed-limit games allow a bet and three raises per street unless told otherwise
+        let raise_cap = match options.get_raise_cap() {
+            0 => 4,
+            raise_cap => raise_cap,
+        };
+        let rake_basis_points = options.get_rake_basis_points();
+        if rake_basis_points > 10000 {
+            return Err(ContractError::new("The rake cannot be more than the whole pot."));
+        }
+        if rake_basis_points > 0 && options.get_operator().is_empty() {
+            return Err(ContractError::new("A raked table needs an operator."));
+        }
+        let min_buy_in = options.get_min_buy_in();
+        let max_buy_in = options.get_max_buy_in();
+        if max_buy_in > 0 && min_buy_in > max_buy_in {
+            return Err(ContractError::new(
+                "The minimum buy-in cannot be more than the maximum.",
+            ));
+        }
 
         //TODO: Review if this is the game state that is trying to be returned.
         return Ok(PokerContract {
-            game_id: blind + max_players + time_per_turn,
+            game_id,
             blind,
+            small_blind_amount,
+            ante,
+            big_blind_ante: options.get_big_blind_ante(),
+            straddle: options.get_straddle(),
             max_players,
             time_per_turn,
-            players: Vec::new(),
+            variant,
+            betting_structure,
+            raise_cap,
+            rake_basis_points,
+            rake_cap: options.get_rake_cap(),
+            no_flop_no_drop: options.get_no_flop_no_drop(),
+            operator: options.get_operator().to_string(),
+            min_buy_in,
+            max_buy_in,
+            house_balance: 0,
+            tournament: tournament.clone(),
+            level: 0,
+            started_at: 0,
+            prize_pool: 0,
+            entrants: 0,
+            results: Vec::new(),
+            tournament_over: false,
+            coordinator: 0,
+            seats: (0..max_players).map(|_| Player::empty()).collect(),
             on_deck: Vec::new(),
+            index: HashMap::new(),
             cards: Vec::new(),
-            deck: Deck::default().iter(),
-            pot: 0,
+            deck: Vec::new(),
+            pots: Vec::new(),
+            contributions: HashMap::new(),
+            dead_money: HashMap::new(),
+            hands: Vec::new(),
+            total_chips: 0,
+            nonces: HashMap::new(),
             min_bet: 0,
+            last_raise: 0,
+            raises: 0,
+            acted: Vec::new(),
             dealer: -1,
+            small_blind: -1,
+            big_blind: -1,
+            next_player: 0,
             last_player: 0,
-            state: GameStage::Join,
+            turn_deadline: 0,
+            stage: GameStage::Join,
+            hand_number: 0,
             seed: [0; 32],
+            pending_history: HandHistory::new(),
+            history: HandHistory::new(),
+            clock: Box::new(EnclaveClock),
         });
     }
 
-    //Allows a player to join a game. If a hand is being played, the player is placed `on_deck`
+    //Allows a player to join a game. The player takes the first empty seat and is dealt
+    //in from the next hand. If the table is full, the player is placed `on_deck`.
+    //Once hands are being played, a new player either posts a big blind to be dealt in
+    //straight away or waits for the big blind to reach his or her seat.
+    //Cash players must bring a deposit within the table's buy-in limits, while
+    //tournament players buy in for the starting stack before the first hand.
+    //Returns whether the player was seated.
     pub fn join_game(
         &mut self,
         msg_sender: &Address,
         deposit: u64,
-        seed: [u8; 32],
+        wait_for_big_blind: bool,
     ) -> Result<bool> {
-        //Validate the seed. (Might not need this)
-        if seed.len() != 32 {
-            return Err(ContractError::new("Invalid format for the random seed."));
-        }
-        for i in 0..32 {
-            self.seed[i] = self.seed[i] ^ seed[i];
+        if self.coordinator != 0 {
+            return Err(ContractError::new(
+                "Players are seated by the tournament coordinator.",
+            ));
         }
+        let started = self.big_blind > -1;
         //Initialize the new player.
-        let new_player = Player {
-            addr: msg_sender.clone(),
-            cards: Vec::new(),
-            action: Action::None,
-            playing: false,
-            bet: 0,
-            balance: deposit,
-        };
+        let mut new_player = Player::new(msg_sender.clone(), deposit);
+        new_player.wait_for_big_blind = started && wait_for_big_blind;
+        new_player.missed_big_blind = started && !wait_for_big_blind;
         //Check that the new player is not already in the game
-        for player in self.players.iter() {
+        for player in self.seats.iter() {
             if msg_sender == player.addr {
                 return Err(ContractError::new("Player is already in the table."));
             }
@@ -96,298 +288,2212 @@ impl<'a> PokerContract<'a> {
                 return Err(ContractError::new("Player is already on deck."));
             }
         }
-        //Take action based on game stage.
-        match self.stage {
-            GameStage::Join => {
-                if self.player.len() < self.max_players {
-                    self.players.push(new_player);
-                    self.index[new_player.addr.to_string()] = self.players.len();
-                    return Ok(true);
-                } else {
-                    self.on_deck.push(new_player);
-                    self.index[new_player.addr.to_string()] = -1;
-                    return Ok(false);
-                }
+        if self.is_tournament() {
+            if self.hand_number > 0 || self.stage != GameStage::Join {
+                return Err(ContractError::new(
+                    "Registration for this tournament has closed.",
+                ));
             }
-            GameStage::Play => {
+            if deposit != self.tournament.get_buy_in() {
+                return Err(ContractError::new(
+                    "The deposit must be the tournament buy-in.",
+                ));
+            }
+            if self.empty_seat().is_none() {
+                return Err(ContractError::new("The tournament is full."));
+            }
+            self.prize_pool += deposit;
+            self.entrants += 1;
+            new_player.balance = self.tournament.get_starting_stack();
+        } else {
+            self.check_buy_in(deposit)?;
+        }
+        self.total_chips += new_player.balance;
+        match self.empty_seat() {
+            Some(seat) => {
+                self.seats[seat] = new_player;
+                self.reindex();
+                return Ok(true);
+            }
+            None => {
                 self.on_deck.push(new_player);
-                self.index[new_player.addr.to_string()] = -1;
+                self.reindex();
                 return Ok(false);
             }
         }
     }
 
-    //Initiates the start of the hand, provided that there is more than one player
-    //joined in the game.
-    pub fn play_hand(&mut self, msg_sender: &Address) -> Result<()> {
-        if self.stage != GameStage::Join {
+    //Sits a player out from the next hand on. A hand in progress is played out as usual.
+    pub fn sit_out(&mut self, msg_sender: &Address) -> Result<()> {
+        let i = self.get_index(msg_sender)? as usize;
+        if self.seats[i].sitting_out {
+            return Err(ContractError::new("Player is already sitting out."));
+        }
+        self.seats[i].sitting_out = true;
+        Ok(())
+    }
+
+    //Deals a player who was sitting out back in from the next hand on.
+    pub fn sit_in(&mut self, msg_sender: &Address) -> Result<()> {
+        let i = self.get_index(msg_sender)? as usize;
+        if !self.seats[i].sitting_out {
+            return Err(ContractError::new("Player is not sitting out."));
+        }
+        self.seats[i].sitting_out = false;
+        Ok(())
+    }
+
+    //Adds `amount` to a cash player's stack, up to the table's maximum buy-in. A seated
+    //player can only top up between hands. A player waiting on deck without chips
+    //tops up to get back into the game, and must bring at least the minimum buy-in.
+    pub fn top_up(&mut self, msg_sender: &Address, amount: u64) -> Result<()> {
+        if self.is_tournament() {
+            return Err(ContractError::new(
+                "Tournament players rebuy or add on instead.",
+            ));
+        }
+        if amount == 0 {
+            return Err(ContractError::new("Cannot top up by nothing."));
+        }
+        let seated = match self.index.get(&msg_sender.to_string()) {
+            Some(i) => *i > -1,
+            None => false,
+        };
+        if seated && !self.between_hands() {
+            return Err(ContractError::new(
+                "Cannot top up while a hand is being played.",
+            ));
+        }
+        let balance = self.find_player(msg_sender)?.balance;
+        if balance == 0 {
+            self.check_buy_in(amount)?;
+        } else if self.max_buy_in > 0 && balance + amount > self.max_buy_in {
+            return Err(ContractError::new(
+                "Cannot top up above the table maximum.",
+            ));
+        }
+        self.find_player(msg_sender)?.balance += amount;
+        self.total_chips += amount;
+        Ok(())
+    }
+
+    //Buys a tournament player another starting stack while rebuys are open. A player
+    //can rebuy between hands once his or her stack is no bigger than the starting
+    //stack, including after busting, and is then seated for the next hand.
+    pub fn rebuy(&mut self, msg_sender: &Address, deposit: u64) -> Result<()> {
+        self.check_purchase(deposit)?;
+        //Rebuys close as soon as the blinds reach the end of the rebuy period
+        self.update_blind_level();
+        if !self.rebuys_open() {
+            return Err(ContractError::new("Rebuys are closed."));
+        }
+        let starting_stack = self.tournament.get_starting_stack();
+        let max_rebuys = self.tournament.get_max_rebuys();
+        {
+            let player = self.find_player(msg_sender)?;
+            if player.balance > starting_stack {
+                return Err(ContractError::new(
+                    "Players can only rebuy once their stack is no bigger than the starting stack.",
+                ));
+            }
+            if max_rebuys > 0 && player.rebuys >= max_rebuys {
+                return Err(ContractError::new("This player has no rebuys left."));
+            }
+            player.rebuys += 1;
+            player.balance += starting_stack;
+        }
+        self.add_to_prize_pool(deposit);
+        self.total_chips += starting_stack;
+        Ok(())
+    }
+
+    //Sells a tournament player the add-on, once, at the last level of the rebuy period.
+    pub fn add_on(&mut self, msg_sender: &Address, deposit: u64) -> Result<()> {
+        self.check_purchase(deposit)?;
+        self.update_blind_level();
+        let chips = self.tournament.get_add_on_chips();
+        if chips == 0 || self.level + 1 != self.tournament.get_rebuy_levels() {
             return Err(ContractError::new(
-                "Cannot call `play_hand` if the game is not in the `Join` stage.",
+                "Add-ons are only sold at the last rebuy level.",
             ));
         }
-        //Add players on deck up to the maximum number of players allowed
-        for i in 0..(self.max_players - self.players.len()) {
-            self.players.append(self.on_deck.remove(i));
+        {
+            let player = self.find_player(msg_sender)?;
+            if player.added_on {
+                return Err(ContractError::new("This player has already added on."));
+            }
+            player.added_on = true;
+            player.balance += chips;
+        }
+        self.add_to_prize_pool(deposit);
+        self.total_chips += chips;
+        Ok(())
+    }
+
+    //Rejects requests that are not meant for this table and hand, or that reuse a
+    //nonce. Every mutating request must carry the sender's next nonce.
+    pub fn check_request(
+        &mut self,
+        msg_sender: &Address,
+        game_id: u64,
+        hand_number: u64,
+        nonce: u64,
+    ) -> Result<()> {
+        if game_id != self.game_id {
+            return Err(ContractError::new("Request is for a different game."));
+        }
+        if hand_number != self.hand_number {
+            return Err(ContractError::new("Request is for a different hand."));
+        }
+        tournament::check_nonce(&mut self.nonces, msg_sender, nonce)
+    }
+
+    //Last nonce the player has used, or 0 if he or she has never made a request.
+    fn get_nonce(&self, addr: &Address) -> u64 {
+        match self.nonces.get(&addr.to_string()) {
+            Some(nonce) => *nonce,
+            None => 0,
+        }
+    }
+
+    //Checks that no chips were created or destroyed: everything deposited and not yet
+    //withdrawn must be in a balance, a bet or a pot.
+    pub fn check_chips(&self) -> Result<()> {
+        let mut chips = self.total_pot() + self.house_balance;
+        for player in self.seats.iter().chain(self.on_deck.iter()) {
+            chips += player.balance + player.bet;
+        }
+        if chips != self.total_chips {
+            return Err(ContractError::new("Chips on the table do not add up."));
+        }
+        Ok(())
+    }
+
+    //Initiates the start of the hand, provided that there is more than one player
+    //joined in the game. Every player in the hand must then commit to a seed and
+    //reveal it before the cards are shuffled and dealt.
+    pub fn play_hand(&mut self, msg_sender: &Address) -> Result<()> {
+        match self.stage {
+            GameStage::Join | GameStage::Showdown => (),
+            _ => {
+                return Err(ContractError::new(
+                    "Cannot call `play_hand` while a hand is being played.",
+                ));
+            }
+        }
+        if self.is_tournament() {
+            if self.tournament_over {
+                return Err(ContractError::new("This tournament is over."));
+            }
+            //The coordinator starts the clock for every table of a multi-table tournament
+            if self.hand_number == 0 && self.coordinator == 0 {
+                self.started_at = self.clock.now();
+            }
+            self.update_blind_level();
+            //Knock out players who did not rebuy before the rebuy period ended
+            if self.hand_number > 0 {
+                self.eliminate_players();
+                if self.tournament_over {
+                    return Ok(());
+                }
+            }
+        }
+        //Players without chips give up their seats and wait on deck until they add more
+        for i in 0..self.seats.len() {
+            if self.seats[i].status != SeatStatus::Empty && self.seats[i].balance == 0 {
+                let player = mem::replace(&mut self.seats[i], Player::empty());
+                self.on_deck.push(player);
+            }
+        }
+        //Seat players on deck in the empty seats
+        let mut i = 0;
+        while i < self.on_deck.len() {
+            match self.empty_seat() {
+                Some(seat) if self.on_deck[i].balance > 0 => {
+                    self.seats[seat] = self.on_deck.remove(i);
+                }
+                Some(_) => i += 1,
+                None => break,
+            }
+        }
+        self.reindex();
+        //Deal in every seated player who is not sitting out. Players waiting for the big
+        //blind are only dealt in once it reaches them, unless there would otherwise be
+        //nobody to play against.
+        let next_big_blind = self.next_ready(self.big_blind);
+        for i in 0..self.seats.len() {
+            let player = &mut self.seats[i];
+            if player.status == SeatStatus::Empty {
+                continue;
+            }
+            player.status = if player.sitting_out
+                || (player.wait_for_big_blind && i as i32 != next_big_blind)
+            {
+                SeatStatus::SittingOut
+            } else {
+                SeatStatus::Active
+            };
+        }
+        if self.players_in_hand() < 2 {
+            for player in self.seats.iter_mut() {
+                if player.status == SeatStatus::SittingOut && !player.sitting_out {
+                    player.status = SeatStatus::Active;
+                }
+            }
         }
         //Check there are at least 2 players
-        if self.players.len() < 2 {
+        if self.players_in_hand() < 2 {
             return Err(ContractError::new(
                 "Cannot call 'play_hand' with less than 2 players.",
             ));
         }
+        //Clear the board, pots and hole cards from the previous hand
+        self.cards.clear();
+        self.pots.clear();
+        self.contributions.clear();
+        self.dead_money.clear();
+        self.hands.clear();
+        for player in self.seats.iter_mut() {
+            player.cards.clear();
+            player.up_cards.clear();
+            player.bet = 0;
+            player.commitment.clear();
+            player.reveal.clear();
+        }
+
+        //Wait for every player to commit to a seed
+        self.stage = GameStage::Commit;
+        self.turn_deadline = self.clock.now() + self.time_per_turn;
+        return Ok(());
+    }
+
+    //Records a player's commitment to his or her seed for this hand: the SHA-256
+    //hash of the 32-byte seed. Once every player has committed, seeds can be revealed.
+    pub fn commit_seed(&mut self, msg_sender: &Address, commitment: &[u8]) -> Result<()> {
+        if self.stage != GameStage::Commit {
+            return Err(ContractError::new(
+                "Cannot call `commit_seed` if the game is not in the `Commit` stage.",
+            ));
+        }
+        if commitment.len() != 32 {
+            return Err(ContractError::new("Invalid format for the seed commitment."));
+        }
+        let i = self.get_index(msg_sender)? as usize;
+        if !self.seats[i].status.in_hand() {
+            return Err(ContractError::new("Player is not dealt into this hand."));
+        }
+        if !self.seats[i].commitment.is_empty() {
+            return Err(ContractError::new("Player has already committed to a seed."));
+        }
+        self.seats[i].commitment = commitment.to_vec();
+
+        if self.seats
+            .iter()
+            .all(|player| !player.status.in_hand() || !player.commitment.is_empty())
+        {
+            self.stage = GameStage::Reveal;
+            self.turn_deadline = self.clock.now() + self.time_per_turn;
+        }
+        Ok(())
+    }
+
+    //Reveals the seed a player committed to. Once every player has revealed, the
+    //seeds are combined, the deck is shuffled and the hand is dealt.
+    pub fn reveal_seed(&mut self, msg_sender: &Address, seed: &[u8]) -> Result<()> {
+        if self.stage != GameStage::Reveal {
+            return Err(ContractError::new(
+                "Cannot call `reveal_seed` if the game is not in the `Reveal` stage.",
+            ));
+        }
+        if seed.len() != 32 {
+            return Err(ContractError::new("Invalid format for the random seed."));
+        }
+        let i = self.get_index(msg_sender)? as usize;
+        if !self.seats[i].status.in_hand() {
+            return Err(ContractError::new("Player is not dealt into this hand."));
+        }
+        if !self.seats[i].reveal.is_empty() {
+            return Err(ContractError::new("Player has already revealed a seed."));
+        }
+        if Sha256::digest(seed).as_slice() != self.seats[i].commitment.as_slice() {
+            return Err(ContractError::new("Seed does not match the commitment."));
+        }
+        self.seats[i].reveal = seed.to_vec();
+
+        if self.seats
+            .iter()
+            .all(|player| !player.status.in_hand() || !player.reveal.is_empty())
+        {
+            return self.deal_hand();
+        }
+        Ok(())
+    }
+
+    //Shuffles the deck using the revealed seeds, posts the blinds and deals the hole cards.
+    fn deal_hand(&mut self) -> Result<()> {
+        //Combine the players' seeds
+        let mut player_seed = [0; 32];
+        for player in self.seats.iter().filter(|player| player.status.in_hand()) {
+            for i in 0..32 {
+                player_seed[i] ^= player.reveal[i];
+            }
+        }
+
+        //Mix in randomness from the enclave and the hand number, so every hand gets
+        //an independent deck even if all of the players collude on their seeds.
+        let mut enclave_seed = [0; 32];
+        get_random_bytes(&mut enclave_seed)?;
+        self.hand_number += 1;
+        self.seed = derive_seed(&enclave_seed, &player_seed, self.hand_number);
+
         //Shuffle the cards.
-        let mut deck = Deck::default().iter().collect();
-        let mut rng: XorShiftRng = SeedableRng::from_seed(&self.seed);
-        rng.shuffle(&mut deck);
-        self.deck = deck.into_iter();
-
-        //Set the dealer
-        self.dealer = (self.dealer + 1) % self.players.len();
-
-        //Pay small and big blinds
-        let small_blind_player = (self.dealer + 1) % self.players.len();
-        let big_blind_player = (self.small_blind_player + 1) % self.players.len();
-        self.players[small_blind_player].bet = self.blind / 2;
-        self.players[small_blind_player].balance -= self.blind / 2;
-        self.players[big_blind_player].bet = self.blind;
-        self.players[big_blind_player].balance -= self.blind;
+        self.deck = shuffle_deck(&self.seed);
+
+        //Commit to the seed before dealing. The inputs and the deck order are only
+        //published once the hand is over.
+        self.pending_history = HandHistory::new();
+        self.pending_history.set_hand_number(self.hand_number);
+        self.pending_history.set_seed_commitment(commit_seed(&self.seed));
+        self.pending_history.set_enclave_seed(enclave_seed.to_vec());
+        self.pending_history.set_variant(self.variant);
+        for player in self.seats.iter().filter(|player| player.status.in_hand()) {
+            let mut reveal = SeedReveal::new();
+            reveal.set_addr(player.addr.to_string());
+            reveal.set_seed(player.reveal.clone());
+            self.pending_history.mut_reveals().push(reveal);
+        }
+        self.pending_history
+            .set_deck(serde_cbor::to_vec(&self.deck).expect("Unable to serialize deck."));
+
+        if self.variant == GameVariant::SEVEN_CARD_STUD {
+            return self.deal_third_street();
+        }
+
+        //Collect the antes, then pay small and big blinds
+        self.move_blinds();
+        self.post_antes();
+        let big_blind_player = self.big_blind as usize;
+        //A player who cannot cover a blind is put all-in for what he or she has
+        if self.seats[self.small_blind as usize].status.in_hand() {
+            let small_blind_player = self.small_blind as usize;
+            let small_blind_amount = self.small_blind_amount;
+            self.post_bet(small_blind_player, small_blind_amount);
+        }
+        let blind = self.blind;
+        self.post_bet(big_blind_player, blind);
+        self.post_missed_blinds();
         self.min_bet = self.blind;
+        self.last_raise = self.blind;
+        self.raises = 1;
+        //The last blind posted has the option to raise when the action gets back to it
+        let last_blind = self.post_straddle().unwrap_or(big_blind_player as i32);
+        self.clear_queued_actions();
+        self.acted.clear();
 
-        //Deal cards
-        let start = (self.dealer + 1) % self.players.len();
-        for i in start..(start + 2 * self.players.len()) {
-            let player = self.players[i % self.players.len()];
-            let next = self.deck.next;
-            match next {
-                Some(card) => player.cards.push(card),
-                () => Err(ContractError::new("Error dealing cards")),
-            }?;
+        //Deal cards one at a time, starting left of the button
+        for _ in 0..self.hole_cards() {
+            self.deal_street(false)?;
         }
 
-        //Set the turn to the next player
-        self.next_player = (big_blind_player + 1) % self.players.len();
-        self.last_player = big_blind_player % self.players.len();
+        //Update game stage to `PreFlop`
+        self.stage = GameStage::PreFlop;
 
-        //Update game stage to `Play`
-        self.stage = GameStage::Play;
+        //Set the turn to the next player
+        self.last_player = last_blind;
+        match self.next_to_act(last_blind) {
+            Some(next) => self.set_turn(next),
+            None => return self.advance_stage(),
+        }
         return Ok(());
     }
 
-    //Allows a player to take an action or store an action to be made when it is a players turn.
+    //Allows a player to take an action on his or her turn. Actions can be queued ahead
+    //of time with `queue_action`.
+    //`value` is the total bet to make for `Bet` and `Raise` and is ignored otherwise.
     //The last player in line will initiate the drawing of the next cards.
     //Illegal actions return a contract error
     pub fn take_action(&mut self, msg_sender: &Address, action: Action, value: u64) -> Result<()> {
-        if self.stage != GameStage::Play {
+        if !self.stage.is_betting_round() {
             return Err(ContractError::new(
-                "Cannot call `take_action` if the game is not in the `Play` stage.",
+                "Cannot call `take_action` if no betting round is in progress.",
             ));
         }
-        if self.index[msg_sender.to_string()] != self.next_player {
+        let player_index = self.get_index(msg_sender)?;
+        if player_index != self.next_player {
             return Err(ContractError::new("Out of turn"));
         }
-        let player_index = self.index[msg_sender.to_string()];
+        let i = player_index as usize;
+        //Acting directly replaces anything the player had queued
+        self.seats[i].action = QueuedAction::None;
         match action {
             Action::None => {
                 return Err(ContractError::new("Invalid action."));
             }
             Action::Check => {
-                if self.min_bet != 0 {
+                if self.seats[i].bet != self.min_bet {
                     return Err(ContractError::new("Invalid move."));
                 }
             }
-            Action::Raise => {
-                if value <= self.min_bet * 2 {
+            Action::Bet => {
+                if self.min_bet > 0 {
+                    return Err(ContractError::new(
+                        "Cannot bet when there is already a bet. Raise instead.",
+                    ));
+                }
+                self.check_raise_to(i, value)?;
+                self.raise_to(i, value);
+            }
+            Action::Call => {
+                let owed = self.min_bet - self.seats[i].bet;
+                if owed == 0 {
+                    return Err(ContractError::new("Nothing to call. Check instead."));
+                }
+                if owed > self.seats[i].balance {
                     return Err(ContractError::new(
-                        "Invalid raise. Must raise by two times the minimum bet.",
+                        "Insufficient balance to call. Go all-in instead.",
                     ));
                 }
-                self.min_bet = value;
-                self.players[player_index].bet = value;
-                self.players[player_index].deposit -= value - self.players[player_index].bet;
-                self.last_player = msg_sender;
+                self.post_bet(i, owed);
             }
-            Action::Match => {
-                self.players[player_index].bet = self.min_bet;
-                self.players[player_index].deposit -= value - self.players[player_index].bet;
+            Action::Raise => {
+                if self.min_bet == 0 {
+                    return Err(ContractError::new("There is no bet to raise. Bet instead."));
+                }
+                self.check_raise_to(i, value)?;
+                self.raise_to(i, value);
+            }
+            Action::AllIn => {
+                if self.seats[i].balance == 0 {
+                    return Err(ContractError::new("No chips left to go all-in with."));
+                }
+                if !self.can_go_all_in(i) {
+                    return Err(ContractError::new(
+                        "Going all-in would break the betting limit. Raise instead.",
+                    ));
+                }
+                let balance = self.seats[i].balance;
+                self.post_bet(i, balance);
+                //Going all-in for more than the current bet gives everyone else the
+                //chance to call it. Only a full raise counts towards the raise cap and
+                //lets players who have already acted raise again.
+                if self.seats[i].bet > self.min_bet {
+                    let raise = self.seats[i].bet - self.min_bet;
+                    if raise >= self.last_raise {
+                        self.last_raise = raise;
+                        self.raises += 1;
+                        self.acted.clear();
+                    }
+                    self.min_bet = self.seats[i].bet;
+                    self.last_player = self.wrap(player_index - 1);
+                }
             }
             Action::Fold => {
                 self.fold_player(player_index)?;
+                if self.players_in_hand() == 1 {
+                    return self.end_uncontested_hand();
+                }
             }
         }
-        self.next_player = (self.next_player + 1) % self.players.len();
 
-        if msg_sender == self.last_player && action != Action::Raise {
-            //Put all bets into the pot
-            for player in self.players.iter() {
-                self.pot += player.bet;
-                player.bet = 0;
-            }
-            //Finalize round or turn card
-            if self.cards.len() == 8 {
-                self.pay_winners();
-                self.stage == GameStage::Join;
-            } else {
-                self.turn_card()?;
-            }
+        let addr = self.seats[i].addr.to_string();
+        if !self.acted.contains(&addr) {
+            self.acted.push(addr);
+        }
+        self.pass_turn(player_index)
+    }
+
+    //Stores an action to be taken automatically when the turn reaches `msg_sender`.
+    //Queued actions only last for the current betting round, and queueing
+    //`QueuedAction::None` clears them.
+    pub fn queue_action(&mut self, msg_sender: &Address, action: QueuedAction) -> Result<()> {
+        if !self.stage.is_betting_round() {
+            return Err(ContractError::new(
+                "Cannot call `queue_action` if no betting round is in progress.",
+            ));
+        }
+        let player_index = self.get_index(msg_sender)?;
+        if player_index == self.next_player {
+            return Err(ContractError::new(
+                "It is already this player's turn. Call `take_action` instead.",
+            ));
         }
+        let i = player_index as usize;
+        if self.seats[i].status != SeatStatus::Active {
+            return Err(ContractError::new(
+                "Only players who can still bet may queue an action.",
+            ));
+        }
+        self.seats[i].action = action;
+        return Ok(());
     }
 
     //Allows a player to leave the game with his or her final balance.
     //If a player is in the middle of the hand, his or her cards are folded.
     //Returns the player's final balance
     pub fn withdraw(&mut self, msg_sender: &Address) -> Result<u64> {
+        if self.coordinator != 0 {
+            return Err(ContractError::new(
+                "Prizes are paid out by the tournament coordinator.",
+            ));
+        }
+        if self.is_tournament() {
+            return self.leave_tournament(msg_sender);
+        }
         //Remove player from current hand.
-        let player_index = self.index[msg_sender.to_string()];
+        let player_index = match self.index.get(&msg_sender.to_string()) {
+            Some(i) => *i,
+            None => -1,
+        };
         if player_index > -1 {
-            //Fold cards
-            return self.fold_player(player_index)?;
+            //Fold cards and give up the seat
+            let in_hand = self.seats[player_index as usize].status.in_hand();
+            if in_hand {
+                self.fold_player(player_index)?;
+            }
+            let player = mem::replace(&mut self.seats[player_index as usize], Player::empty());
+            self.reindex();
+            if in_hand && self.stage.is_betting_round() {
+                if self.players_in_hand() == 1 {
+                    self.end_uncontested_hand()?;
+                } else if player_index == self.next_player {
+                    self.pass_turn(player_index)?;
+                }
+            } else if in_hand && self.players_in_hand() < 2 {
+                //The cards have not been dealt yet, so there is no pot to award
+                match self.stage {
+                    GameStage::Commit | GameStage::Reveal => self.stage = GameStage::Join,
+                    _ => (),
+                }
+            }
+            self.total_chips -= player.balance;
+            return Ok(player.balance);
         } else {
             //Remove player from waiting
             for i in 0..self.on_deck.len() {
                 if msg_sender == self.on_deck[i].addr {
-                    return Ok(self.on_deck.remove(i).balance);
+                    let player = self.on_deck.remove(i);
+                    self.reindex();
+                    self.total_chips -= player.balance;
+                    return Ok(player.balance);
                 }
             }
         }
         return Err(ContractError::new("This player has not joined the game."));
     }
 
+    //Lets a player leave a tournament. The buy-in is refunded if no hand has been
+    //played yet, and otherwise a player can only leave once he or she has finished,
+    //collecting his or her prize. Returns the amount paid out.
+    fn leave_tournament(&mut self, msg_sender: &Address) -> Result<u64> {
+        let payout = if self.hand_number == 0 && self.stage == GameStage::Join {
+            let buy_in = self.tournament.get_buy_in();
+            self.get_index(msg_sender)?;
+            self.prize_pool -= buy_in;
+            self.entrants -= 1;
+            buy_in
+        } else {
+            let result = self.results
+                .iter()
+                .position(|result| result.get_addr() == msg_sender.to_string());
+            match result {
+                Some(i) if !self.results[i].get_claimed() => {
+                    self.results[i].set_claimed(true);
+                    self.results[i].get_prize()
+                }
+                _ => {
+                    return Err(ContractError::new(
+                        "Cannot leave a tournament before finishing it.",
+                    ));
+                }
+            }
+        };
+        //A player who is still seated gives up the seat along with his or her chips
+        if let Ok(i) = self.get_index(msg_sender) {
+            let player = mem::replace(&mut self.seats[i as usize], Player::empty());
+            self.reindex();
+            self.total_chips -= player.balance;
+        }
+        return Ok(payout);
+    }
+
+    //Allows the operator to withdraw the rake collected by the house.
+    //Returns the amount withdrawn
+    pub fn withdraw_rake(&mut self, msg_sender: &Address) -> Result<u64> {
+        if self.operator.is_empty() || msg_sender.to_string() != self.operator {
+            return Err(ContractError::new("Only the operator can withdraw the rake."));
+        }
+        let amount = self.house_balance;
+        self.house_balance = 0;
+        self.total_chips -= amount;
+        return Ok(amount);
+    }
+
+    //Allows any seated player to move the game along once the player whose turn it
+    //is has run out of time. The stalled player checks if possible and folds otherwise.
+    //While seeds are being committed or revealed, players who have not done so are
+    //sat out of the hand and their seeds are left out of the shuffle.
+    pub fn claim_timeout(&mut self, msg_sender: &Address) -> Result<()> {
+        self.get_index(msg_sender)?;
+        if self.clock.now() < self.turn_deadline {
+            return Err(ContractError::new("The current turn has not timed out yet."));
+        }
+        if self.stage == GameStage::Commit || self.stage == GameStage::Reveal {
+            return self.sit_out_stalled_seeds();
+        }
+        if !self.stage.is_betting_round() {
+            return Err(ContractError::new(
+                "Cannot call `claim_timeout` if no betting round is in progress.",
+            ));
+        }
+        let stalled = self.seats[self.next_player as usize].addr.clone();
+        let action = if self.seats[self.next_player as usize].bet == self.min_bet {
+            Action::Check
+        } else {
+            Action::Fold
+        };
+        self.take_action(&stalled, action, 0)
+    }
+
+    //Replaces the time source used for turn deadlines.
+    #[cfg(test)]
+    pub fn set_clock(&mut self, clock: Box<Clock>) {
+        self.clock = clock;
+    }
+
     //+++++++++++++++++++++++++++++++++++++++++++++++++++++
     // HELPER FUNCTIONS
     //+++++++++++++++++++++++++++++++++++++++++++++++++++++
 
-    fn turn_card(&mut self) -> Result<()> {
-        self.deck.next();
-        let next = self.deck.next();
-        match next {
-            Some(card) => {
-                self.cards.push(card);
-                Ok(())
+    //Sits out players who failed to commit or reveal a seed in time. The hand goes
+    //ahead without them, or is called off if fewer than two players are left.
+    fn sit_out_stalled_seeds(&mut self) -> Result<()> {
+        let committing = self.stage == GameStage::Commit;
+        for player in self.seats.iter_mut() {
+            let stalled = if committing {
+                player.commitment.is_empty()
+            } else {
+                player.reveal.is_empty()
+            };
+            if player.status.in_hand() && stalled {
+                player.status = SeatStatus::SittingOut;
             }
-            () => Err(ContractError::new(
-                "Error in turning the cards. Deck is empty.",
-            )),
         }
+
+        if self.players_in_hand() < 2 {
+            self.stage = GameStage::Join;
+            return Ok(());
+        }
+        if committing {
+            self.stage = GameStage::Reveal;
+            self.turn_deadline = self.clock.now() + self.time_per_turn;
+            return Ok(());
+        }
+        self.deal_hand()
     }
 
-    fn fold_player(&mut self, player_index: i32) -> Result<u64> {
-        self.pot += self.players[player_index].bet;
-        self.players[player_index].bet = 0;
-        for i in player_index + 1..self.players.len() {
-            self.index[self.players[i].addr.to_string()] -= 1;
+    //Seated or waiting player with the address `msg_sender`.
+    fn find_player(&mut self, msg_sender: &Address) -> Result<&mut Player> {
+        let player = self.seats
+            .iter_mut()
+            .chain(self.on_deck.iter_mut())
+            .find(|player| *msg_sender == player.addr);
+        match player {
+            Some(player) => Ok(player),
+            None => Err(ContractError::new("This player has not joined the game.")),
         }
-        Ok(self.players.remove(player_index).balance)
     }
 
-    fn pay_winners(&mut self) -> Result<()> {
-        let winners: Vec<Player> = Vec::new();
-        let max = 0;
-        for player in self.players.iter() {
-            let rank = Hand::new_with_cards(&player.cards).rank();
-            if rank > max {
-                winners.clear();
-                winners.push(player);
-                max = rank;
-            } else if rank == max {
-                winners.push(player);
-            }
-        }
-        for player in self.winners.iter() {
-            player.balance += self.pot / winners.len();
-        }
-        self.pot = 0;
+    //Checks what a cash player sits down with against the table's buy-in limits.
+    fn check_buy_in(&self, deposit: u64) -> Result<()> {
+        if deposit < self.min_buy_in {
+            return Err(ContractError::new("The deposit is below the minimum buy-in."));
+        }
+        if self.max_buy_in > 0 && deposit > self.max_buy_in {
+            return Err(ContractError::new("The deposit is above the maximum buy-in."));
+        }
         Ok(())
     }
 
-    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
-    // FUNCTIONS TO REQUEST AND FORMAT STATE
-    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+    //Rebuilds the address index. Seated players map to their seat number and players
+    //on deck map to -1.
+    fn reindex(&mut self) {
+        self.index.clear();
+        for (i, player) in self.seats.iter().enumerate() {
+            if player.status != SeatStatus::Empty {
+                self.index.insert(player.addr.to_string(), i as i32);
+            }
+        }
+        for waiting in self.on_deck.iter() {
+            self.index.insert(waiting.addr.to_string(), -1);
+        }
+    }
 
-    pub fn get_public_state(&mut self) -> Result<PublicState> {
-        let mut state = PublicState::new();
+    fn get_index(&self, msg_sender: &Address) -> Result<i32> {
+        match self.index.get(&msg_sender.to_string()) {
+            Some(i) if *i > -1 => Ok(*i),
+            _ => Err(ContractError::new("This player is not seated at the table.")),
+        }
+    }
 
-        state.set_game_id(self.game_id);
-        state.set_blind(self.blind);
-        state.set_max_players(self.max_players);
-        state.set_players(self.serialize_players(&self.players));
-        state.set_on_deck(self.serialize_players(&self.on_deck));
-        state.set_pot(self.pot);
-        state.set_min_bet(self.min_bet);
-        state.set_dealer(self.dealer);
-        state.set_next_player(self.next_player);
-        state.set_last_player(self.set_last_player);
-        state.set_stage(self.stage);
+    //Wraps a possibly negative position around the number of seats.
+    fn wrap(&self, position: i32) -> i32 {
+        let len = self.seats.len() as i32;
+        ((position % len) + len) % len
+    }
 
-        Ok(state)
+    //First empty seat, if the table is not full.
+    fn empty_seat(&self) -> Option<usize> {
+        self.seats
+            .iter()
+            .position(|player| player.status == SeatStatus::Empty)
     }
 
-    fn get_player_state(&mut self, msg_sender: &Address) -> Result<PlayerState> {
-        let player = self.players[0];
-        let mut state = PlayerState::new();
+    //First seat after `seat` whose player is still in the hand.
+    fn next_in_hand(&self, seat: i32) -> i32 {
+        let mut position = seat;
+        for _ in 0..self.seats.len() {
+            position = self.wrap(position + 1);
+            if self.seats[position as usize].status.in_hand() {
+                return position;
+            }
+        }
+        position
+    }
 
-        state.set_addr(player.addr.to_string());
-        state.set_action(player.action.to_string());
-        state.set_cards(serde_cbor::to_vec(&player.cards).expect("Unable to serialize cards."));
-        state.set_playing(player.playing);
-        state.set_bet(player.bet);
-        state.set_balance(player.balance);
+    //Last seat before `seat` whose player is still in the hand.
+    fn previous_in_hand(&self, seat: i32) -> i32 {
+        let mut position = seat;
+        for _ in 0..self.seats.len() {
+            position = self.wrap(position - 1);
+            if self.seats[position as usize].status.in_hand() {
+                return position;
+            }
+        }
+        position
+    }
 
-        Ok(state)
+    //First seat after `seat` whose player could be dealt into the next hand, counting
+    //players who are waiting for the big blind.
+    fn next_ready(&self, seat: i32) -> i32 {
+        let mut position = seat;
+        for _ in 0..self.seats.len() {
+            position = self.wrap(position + 1);
+            let player = &self.seats[position as usize];
+            if player.status != SeatStatus::Empty && !player.sitting_out && player.balance > 0 {
+                return position;
+            }
+        }
+        position
     }
 
-    pub fn from_player_state(state: &PlayerState) -> Player {}
+    //Number of players who have not folded or sat out of the hand.
+    fn players_in_hand(&self) -> usize {
+        self.seats
+            .iter()
+            .filter(|player| player.status.in_hand())
+            .count()
+    }
 
-    fn serialize_players(&mut self, players: &Vec<Player>) -> Vec<PlayerState> {
-        let formatted_players: Vec<PlayerState> = Vec::new();
-        for player in players {
-            let state = self.get_player_state(&player.addr).unwrap();
-            formatted_players.push(state);
+    fn deal_card(&mut self) -> Result<Card> {
+        match self.deck.pop() {
+            Some(card) => Ok(card),
+            None => Err(ContractError::new("Error dealing cards. Deck is empty.")),
         }
-        return formatted_players;
     }
-}
 
-impl<'a> Contract<PokerState> for PokerContract<'a> {
-    /// Get serializable contract state.
+    //Number of hole cards each player is dealt.
+    fn hole_cards(&self) -> usize {
+        match self.variant {
+            GameVariant::TEXAS_HOLDEM => 2,
+            GameVariant::OMAHA => 4,
+            //Dealt face down on third street
+            GameVariant::SEVEN_CARD_STUD => 2,
+        }
+    }
+
+    //Starts a stud hand. After the antes every player is dealt two cards down and one
+    //up, and the lowest up card brings in the betting. The bring-in does not get to
+    //act again unless someone completes the bet to a full small bet.
+    fn deal_third_street(&mut self) -> Result<()> {
+        //There is no button in stud, but one still moves round the table to decide who
+        //is dealt to first and who gets the odd chips of a split pot
+        self.dealer = self.next_in_hand(self.dealer);
+        self.post_antes();
+        for _ in 0..self.hole_cards() {
+            self.deal_street(false)?;
+        }
+        self.deal_street(true)?;
+
+        let bring_in_player = self.bring_in_seat();
+        let bring_in = self.small_blind_amount;
+        self.post_bet(bring_in_player, bring_in);
+        self.min_bet = bring_in;
+        self.last_raise = self.blind;
+        self.raises = 0;
+        self.clear_queued_actions();
+        self.acted.clear();
+        self.stage = GameStage::ThirdStreet;
+
+        self.last_player = self.wrap(bring_in_player as i32 - 1);
+        match self.next_to_act(bring_in_player as i32) {
+            Some(next) => self.set_turn(next),
+            None => return self.advance_stage(),
+        }
+        return Ok(());
+    }
+
+    //Deals every player still in the hand one more card, face up or down, starting
+    //left of the button. The order is recorded so the deal can be checked against the
+    //deck once the hand is over.
+    fn deal_street(&mut self, face_up: bool) -> Result<()> {
+        let mut round = DealRound::new();
+        let mut seat = self.dealer;
+        for _ in 0..self.players_in_hand() {
+            seat = self.next_in_hand(seat);
+            let card = self.deal_card()?;
+            let player = &mut self.seats[seat as usize];
+            if face_up {
+                player.up_cards.push(card);
+            } else {
+                player.cards.push(card);
+            }
+            round.mut_players().push(player.addr.to_string());
+        }
+        self.pending_history.mut_rounds().push(round);
+        Ok(())
+    }
+
+    //Seat of the stud player with the lowest up card, who posts the bring-in.
+    fn bring_in_seat(&self) -> usize {
+        let mut lowest: Option<usize> = None;
+        for i in 0..self.seats.len() {
+            if !self.seats[i].status.in_hand() {
+                continue;
+            }
+            let order = bring_in_order(&self.seats[i].up_cards[0]);
+            lowest = match lowest {
+                Some(j) if bring_in_order(&self.seats[j].up_cards[0]) < order => Some(j),
+                _ => Some(i),
+            };
+        }
+        lowest.unwrap_or(0)
+    }
+
+    //Seat of the stud player showing the best hand among those who can still bet, who
+    //acts first from fourth street on. Ties go to the player closest to the left of
+    //the button.
+    fn best_showing(&self) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for i in 0..self.seats.len() {
+            if self.seats[i].status != SeatStatus::Active {
+                continue;
+            }
+            best = match best {
+                Some(j) => {
+                    let order = showing_strength(&self.seats[i].up_cards)
+                        .cmp(&showing_strength(&self.seats[j].up_cards));
+                    let closer = self.seats_from_button(i) < self.seats_from_button(j);
+                    if order == Ordering::Greater || (order == Ordering::Equal && closer) {
+                        Some(i)
+                    } else {
+                        Some(j)
+                    }
+                }
+                None => Some(i),
+            };
+        }
+        best
+    }
+
+    //Burns one card and turns `count` cards onto the board.
+    fn turn_cards(&mut self, count: usize) -> Result<()> {
+        self.deal_card()?;
+        for _ in 0..count {
+            let card = self.deal_card()?;
+            self.cards.push(card);
+        }
+        Ok(())
+    }
+
+    //Moves the button and blinds for a new hand under the dead button rule. The big
+    //blind moves to the next seat dealt in, and the small blind and the button follow
+    //onto the seats that held the blinds before, even if those players are no longer
+    //in the hand. Players the blinds pass over owe them when they are next dealt in.
+    //Heads-up, the button posts the small blind and acts first before the flop.
+    fn move_blinds(&mut self) {
+        let heads_up = self.players_in_hand() == 2;
+        if self.big_blind < 0 {
+            //First hand at the table
+            self.dealer = self.next_in_hand(-1);
+            self.small_blind = if heads_up {
+                self.dealer
+            } else {
+                self.next_in_hand(self.dealer)
+            };
+            self.big_blind = self.next_in_hand(self.small_blind);
+            return;
+        }
+
+        let previous = self.big_blind;
+        let big_blind = self.next_in_hand(previous);
+        //Players sitting out where the blinds fall have missed them
+        let mut seat = self.wrap(previous + 1);
+        while seat != big_blind {
+            if self.seats[seat as usize].status == SeatStatus::SittingOut {
+                self.seats[seat as usize].missed_big_blind = true;
+            }
+            seat = self.wrap(seat + 1);
+        }
+        if self.seats[previous as usize].status == SeatStatus::SittingOut {
+            self.seats[previous as usize].missed_small_blind = true;
+        }
+
+        if heads_up {
+            self.dealer = self.next_in_hand(big_blind);
+            self.small_blind = self.dealer;
+        } else {
+            let mut small_blind = previous;
+            let mut dealer = self.small_blind;
+            //The big blind has caught up with the button, so close the blinds up behind it
+            if dealer == big_blind {
+                small_blind = self.previous_in_hand(big_blind);
+                dealer = self.previous_in_hand(small_blind);
+            }
+            self.small_blind = small_blind;
+            self.dealer = dealer;
+        }
+        self.big_blind = big_blind;
+    }
+
+    //Collects the blinds owed by players who missed them. A missed big blind is posted
+    //live, counting towards the player's bet, and a missed small blind goes into the
+    //pot as dead money. The player in the big blind owes nothing extra.
+    fn post_missed_blinds(&mut self) {
+        for i in 0..self.seats.len() {
+            if !self.seats[i].status.in_hand() {
+                continue;
+            }
+            if i as i32 != self.big_blind {
+                if self.seats[i].missed_big_blind && self.seats[i].bet < self.blind {
+                    let owed = self.blind - self.seats[i].bet;
+                    self.post_bet(i, owed);
+                }
+                if self.seats[i].missed_small_blind {
+                    let small_blind_amount = self.small_blind_amount;
+                    self.post_dead(i, small_blind_amount);
+                }
+            }
+            let player = &mut self.seats[i];
+            player.missed_small_blind = false;
+            player.missed_big_blind = false;
+            player.wait_for_big_blind = false;
+        }
+        self.build_pots();
+    }
+
+    //Collects the antes as dead money. Every player dealt in posts `ante`, or with a
+    //big blind ante the player in the big blind posts it once for the whole table.
+    fn post_antes(&mut self) {
+        if self.ante == 0 {
+            return;
+        }
+        let ante = self.ante;
+        if self.big_blind_ante {
+            let big_blind_player = self.big_blind as usize;
+            self.post_dead(big_blind_player, ante);
+        } else {
+            for i in 0..self.seats.len() {
+                if self.seats[i].status.in_hand() {
+                    self.post_dead(i, ante);
+                }
+            }
+        }
+        self.build_pots();
+    }
+
+    //Has the player after the big blind post a live straddle of two big blinds, if the
+    //table allows it. There is no straddle heads-up. Returns the straddler's seat.
+    fn post_straddle(&mut self) -> Option<i32> {
+        if !self.straddle || self.players_in_hand() < 3 {
+            return None;
+        }
+        let straddler = self.next_in_hand(self.big_blind);
+        if self.seats[straddler as usize].status != SeatStatus::Active {
+            return None;
+        }
+        let straddle = 2 * self.blind;
+        let owed = straddle.saturating_sub(self.seats[straddler as usize].bet);
+        self.post_bet(straddler as usize, owed);
+        //The straddle acts as a third blind, so raises are sized from it
+        self.min_bet = max(self.min_bet, self.seats[straddler as usize].bet);
+        self.last_raise = straddle;
+        self.raises += 1;
+        Some(straddler)
+    }
+
+    //Moves up to `amount` chips from a player's balance straight into the pot, without
+    //counting towards his or her bet. Used for antes and dead blinds.
+    fn post_dead(&mut self, player_index: usize, amount: u64) {
+        let player = &mut self.seats[player_index];
+        let amount = min(amount, player.balance);
+        player.balance -= amount;
+        if player.balance == 0 {
+            player.status = SeatStatus::AllIn;
+        }
+        *self.dead_money.entry(player.addr.to_string()).or_insert(0) += amount;
+    }
+
+    //Moves up to `amount` chips from a player's balance into his or her bet. A player
+    //whose balance runs out is marked as all-in.
+    fn post_bet(&mut self, player_index: usize, amount: u64) {
+        let player = &mut self.seats[player_index];
+        let amount = min(amount, player.balance);
+        player.balance -= amount;
+        player.bet += amount;
+        if player.balance == 0 {
+            player.status = SeatStatus::AllIn;
+        }
+    }
+
+    //Finds the first player after `player_index` who can still bet, or `None` if the
+    //turn passes `last_player` before anyone is found.
+    fn next_to_act(&self, player_index: i32) -> Option<i32> {
+        let mut position = player_index;
+        for _ in 0..self.seats.len() {
+            position = self.wrap(position + 1);
+            if self.seats[position as usize].status == SeatStatus::Active {
+                return Some(position);
+            }
+            if position == self.last_player {
+                return None;
+            }
+        }
+        None
+    }
+
+    //Size of a bet or raise in fixed-limit games: the big blind on the first two
+    //streets and twice that on the turn and river. In stud, the small bet is used on
+    //third and fourth street and the big bet from fifth street on.
+    fn bet_size(&self) -> u64 {
+        match self.stage {
+            GameStage::Turn
+            | GameStage::River
+            | GameStage::FifthStreet
+            | GameStage::SixthStreet
+            | GameStage::SeventhStreet => 2 * self.blind,
+            _ => self.blind,
+        }
+    }
+
+    //Smallest and largest total bets a player may raise to under the table's betting
+    //structure, or `None` if no more raises are allowed on this street or the player
+    //has acted since the last full bet or raise. The player may not have enough chips
+    //to make either.
+    fn raise_limits(&self, player_index: usize) -> Option<(u64, u64)> {
+        let player = &self.seats[player_index];
+        if self.acted.contains(&player.addr.to_string()) {
+            return None;
+        }
+        match self.betting_structure {
+            BettingStructure::NO_LIMIT => Some((
+                self.min_bet + self.last_raise,
+                player.bet + player.balance,
+            )),
+            BettingStructure::POT_LIMIT => {
+                //The largest raise is the size of the pot after calling
+                let owed = self.min_bet - player.bet;
+                let bets: u64 = self.seats.iter().map(|p| p.bet).sum();
+                let pot_after_call = self.total_pot() + bets + owed;
+                Some((
+                    self.min_bet + self.last_raise,
+                    self.min_bet + pot_after_call,
+                ))
+            }
+            BettingStructure::FIXED_LIMIT => {
+                if self.raises >= self.raise_cap {
+                    return None;
+                }
+                //A bet smaller than a full bet, like the stud bring-in, is completed to a
+                //full bet rather than raised
+                let raise_to = if self.min_bet < self.bet_size() {
+                    self.bet_size()
+                } else {
+                    self.min_bet + self.bet_size()
+                };
+                Some((raise_to, raise_to))
+            }
+        }
+    }
+
+    //Checks that a player can bet or raise to `value` under the table's betting structure.
+    fn check_raise_to(&self, player_index: usize, value: u64) -> Result<()> {
+        let name = if self.min_bet == 0 { "bet" } else { "raise" };
+        if self.acted.contains(&self.seats[player_index].addr.to_string()) {
+            return Err(ContractError::new(
+                "The betting has not been reopened by a full raise. Call or fold instead.",
+            ));
+        }
+        let (least, most) = match self.raise_limits(player_index) {
+            Some(limits) => limits,
+            None => {
+                return Err(ContractError::new(
+                    "No more raises are allowed on this street.",
+                ));
+            }
+        };
+        if self.betting_structure == BettingStructure::FIXED_LIMIT && value != least {
+            return Err(ContractError::new(&format!(
+                "Invalid {}. Fixed-limit {}s must be to exactly {}.",
+                name, name, least
+            )));
+        }
+        if value < least {
+            return Err(ContractError::new(&format!(
+                "Invalid {}. Must {} to at least {}.",
+                name, name, least
+            )));
+        }
+        let player = &self.seats[player_index];
+        if value - player.bet > player.balance {
+            return Err(ContractError::new(&format!(
+                "Insufficient balance to {}. Go all-in instead.",
+                name
+            )));
+        }
+        if value > most {
+            return Err(ContractError::new(&format!(
+                "Invalid {}. Pot-limit {}s can be to at most {}.",
+                name, name, most
+            )));
+        }
+        return Ok(());
+    }
+
+    //Makes a full bet or raise to `value`, reopening the betting.
+    fn raise_to(&mut self, player_index: usize, value: u64) {
+        let owed = value - self.seats[player_index].bet;
+        self.post_bet(player_index, owed);
+        self.last_raise = value - self.min_bet;
+        self.raises += 1;
+        self.acted.clear();
+        self.min_bet = value;
+        self.last_player = self.wrap(player_index as i32 - 1);
+    }
+
+    //Whether a player's all-in stays within the betting limits. Going all-in for no
+    //more than a call is always allowed.
+    fn can_go_all_in(&self, player_index: usize) -> bool {
+        let player = &self.seats[player_index];
+        let all_in = player.bet + player.balance;
+        if all_in <= self.min_bet {
+            return true;
+        }
+        match self.raise_limits(player_index) {
+            Some((_, most)) => all_in <= most,
+            None => false,
+        }
+    }
+
+    //Gives the turn to a player, who then has `time_per_turn` seconds to act.
+    fn set_turn(&mut self, player_index: i32) {
+        self.next_player = player_index;
+        self.turn_deadline = self.clock.now() + self.time_per_turn;
+    }
+
+    //Moves the turn on from `player_index`. Players who are all-in are skipped, and
+    //the betting round is closed once the turn passes `last_player`.
+    fn pass_turn(&mut self, player_index: i32) -> Result<()> {
+        if player_index != self.last_player {
+            if let Some(next) = self.next_to_act(player_index) {
+                self.set_turn(next);
+                return self.play_queued_action();
+            }
+        }
+        self.advance_stage()
+    }
+
+    //Takes the action queued by the player whose turn it is, unless the betting has
+    //changed so that it is no longer safe to make. The player then acts as usual.
+    fn play_queued_action(&mut self) -> Result<()> {
+        let i = self.next_player as usize;
+        let queued = self.seats[i].action;
+        self.seats[i].action = QueuedAction::None;
+        let owed = self.min_bet - self.seats[i].bet;
+        let action = match queued {
+            QueuedAction::None => return Ok(()),
+            QueuedAction::CheckFold => if owed == 0 { Action::Check } else { Action::Fold },
+            QueuedAction::CallAny => {
+                if owed == 0 {
+                    Action::Check
+                } else if owed < self.seats[i].balance {
+                    Action::Call
+                } else {
+                    //Going all-in is left to the player
+                    return Ok(());
+                }
+            }
+            QueuedAction::FoldToBet => if owed == 0 { return Ok(()) } else { Action::Fold },
+        };
+        let addr = self.seats[i].addr.clone();
+        self.take_action(&addr, action, 0)
+    }
+
+    //Forgets every queued action once the betting round they were made for is over.
+    fn clear_queued_actions(&mut self) {
+        for player in self.seats.iter_mut() {
+            player.action = QueuedAction::None;
+        }
+    }
+
+    //Number of players in the hand who can still bet.
+    fn players_to_act(&self) -> usize {
+        self.seats
+            .iter()
+            .filter(|player| player.status == SeatStatus::Active)
+            .count()
+    }
+
+    //Moves every outstanding bet into the pots.
+    fn collect_bets(&mut self) {
+        for player in self.seats.iter_mut() {
+            *self.contributions.entry(player.addr.to_string()).or_insert(0) += player.bet;
+            player.bet = 0;
+        }
+        self.build_pots();
+    }
+
+    //Rebuilds the main pot and the side pots from the live chips committed to the
+    //hand. A new side pot starts above every all-in amount, and only players still in
+    //the hand who covered that amount are eligible to win it. Dead money goes to the
+    //main pot. Chips nobody still in the hand covered go to the pot below them.
+    fn build_pots(&mut self) {
+        let mut levels: Vec<u64> = Vec::new();
+        for player in self.seats.iter() {
+            if player.status == SeatStatus::AllIn {
+                levels.push(self.committed_live(&player.addr));
+            }
+        }
+        levels.push(self.contributions.values().cloned().max().unwrap_or(0));
+        levels.sort();
+        levels.dedup();
+
+        self.pots.clear();
+        let mut dead: u64 = self.dead_money.values().sum();
+        let mut floor = 0;
+        for level in levels {
+            let mut amount = mem::replace(&mut dead, 0);
+            for contribution in self.contributions.values() {
+                amount += min(*contribution, level) - min(*contribution, floor);
+            }
+            let mut eligible = Vec::new();
+            for player in self.seats.iter() {
+                if player.status.in_hand() && self.committed_live(&player.addr) >= level {
+                    eligible.push(player.addr.to_string());
+                }
+            }
+            floor = level;
+            if amount == 0 {
+                continue;
+            }
+            if eligible.is_empty() {
+                if let Some(pot) = self.pots.last_mut() {
+                    pot.amount += amount;
+                    continue;
+                }
+            }
+            self.pots.push(Pot { amount, eligible });
+        }
+    }
+
+    //Chips a player has put into the pots during this hand, dead money included.
+    fn committed(&self, addr: &Address) -> u64 {
+        let dead = match self.dead_money.get(&addr.to_string()) {
+            Some(amount) => *amount,
+            None => 0,
+        };
+        self.committed_live(addr) + dead
+    }
+
+    //Live chips a player has put into the pots during this hand.
+    fn committed_live(&self, addr: &Address) -> u64 {
+        match self.contributions.get(&addr.to_string()) {
+            Some(amount) => *amount,
+            None => 0,
+        }
+    }
+
+    //Number of seats between the button and a player, counting clockwise from the
+    //first seat after the button.
+    fn seats_from_button(&self, player_index: usize) -> i32 {
+        self.wrap(player_index as i32 - self.dealer - 1)
+    }
+
+    //Total of the main pot and all side pots.
+    fn total_pot(&self) -> u64 {
+        self.pots.iter().map(|pot| pot.amount).sum()
+    }
+
+    //Closes the current betting round and deals the next street. After the river
+    //the hand moves to `Showdown` and the pots are paid out. If fewer than two
+    //players can still bet, the remaining streets are dealt without betting.
+    fn advance_stage(&mut self) -> Result<()> {
+        self.collect_bets();
+        self.clear_queued_actions();
+        self.acted.clear();
+        self.min_bet = 0;
+        self.last_raise = self.blind;
+        self.raises = 0;
+        self.last_player = self.wrap(self.dealer);
+        match self.stage {
+            GameStage::PreFlop => {
+                self.turn_cards(3)?;
+                self.stage = GameStage::Flop;
+            }
+            GameStage::Flop => {
+                self.turn_cards(1)?;
+                self.stage = GameStage::Turn;
+            }
+            GameStage::Turn => {
+                self.turn_cards(1)?;
+                self.stage = GameStage::River;
+            }
+            GameStage::River | GameStage::SeventhStreet => {
+                self.stage = GameStage::Showdown;
+                return self.pay_winners();
+            }
+            GameStage::ThirdStreet => {
+                self.deal_street(true)?;
+                self.stage = GameStage::FourthStreet;
+            }
+            GameStage::FourthStreet => {
+                self.deal_street(true)?;
+                self.stage = GameStage::FifthStreet;
+            }
+            GameStage::FifthStreet => {
+                self.deal_street(true)?;
+                self.stage = GameStage::SixthStreet;
+            }
+            GameStage::SixthStreet => {
+                self.deal_street(false)?;
+                self.stage = GameStage::SeventhStreet;
+            }
+            _ => {
+                return Err(ContractError::new("No betting round is in progress."));
+            }
+        }
+        //In stud the best hand showing acts first
+        if self.variant == GameVariant::SEVEN_CARD_STUD {
+            if let Some(first) = self.best_showing() {
+                self.last_player = self.wrap(first as i32 - 1);
+            }
+        }
+        if self.players_to_act() < 2 {
+            return self.advance_stage();
+        }
+        match self.next_to_act(self.last_player) {
+            Some(next) => self.set_turn(next),
+            None => return self.advance_stage(),
+        }
+        Ok(())
+    }
+
+    //Awards the pots to the only player left in the hand. No cards are shown.
+    fn end_uncontested_hand(&mut self) -> Result<()> {
+        self.collect_bets();
+        self.return_uncalled_bet();
+        let winner = self.next_in_hand(self.dealer) as usize;
+        let pot = self.total_pot();
+        let rake = self.take_rake(pot, 0);
+        self.seats[winner].balance += pot - rake;
+        self.pots.clear();
+        self.min_bet = 0;
+        self.stage = GameStage::Join;
+        self.publish_history();
+        if self.is_tournament() {
+            self.eliminate_players();
+        }
+        Ok(())
+    }
+
+    //Publishes the shuffle inputs and deck order of the hand that just ended, along
+    //with the board and the hands shown down, so that the players dealt in can check
+    //the deal with `poker_api::verify_shuffle`.
+    fn publish_history(&mut self) {
+        self.history = self.pending_history.clone();
+        self.history
+            .set_board(serde_cbor::to_vec(&self.cards).expect("Unable to serialize cards."));
+        if self.stage == GameStage::Showdown {
+            for player in self.seats.iter().filter(|player| player.status.in_hand()) {
+                let mut shown = ShownHand::new();
+                shown.set_addr(player.addr.to_string());
+                shown.set_cards(
+                    serde_cbor::to_vec(&player.cards).expect("Unable to serialize cards."),
+                );
+                shown.set_up_cards(
+                    serde_cbor::to_vec(&player.up_cards).expect("Unable to serialize cards."),
+                );
+                self.history.mut_shown().push(shown);
+            }
+        }
+        self.pending_history = HandHistory::new();
+    }
+
+    //Takes a player out of the hand, putting his or her bet in the pots. The player
+    //keeps his or her seat, so the button and turn order are unaffected.
+    fn fold_player(&mut self, player_index: i32) -> Result<()> {
+        let i = player_index as usize;
+        if !self.seats[i].status.in_hand() {
+            return Err(ContractError::new("Player is not in the hand."));
+        }
+        let bet = self.seats[i].bet;
+        *self.contributions.entry(self.seats[i].addr.to_string()).or_insert(0) += bet;
+        self.seats[i].bet = 0;
+        self.seats[i].status = SeatStatus::Folded;
+        self.build_pots();
+        Ok(())
+    }
+
+    //Evaluates the best five-card hand of every player left in the hand, using his
+    //or her hole cards together with the board. In Omaha a hand must use exactly two
+    //hole cards and three board cards.
+    fn evaluate_hands(&mut self) -> Result<()> {
+        self.hands.clear();
+        for player in self.seats.iter().filter(|player| player.status.in_hand()) {
+            let best_hand = match self.variant {
+                GameVariant::TEXAS_HOLDEM => {
+                    let mut cards = player.cards.clone();
+                    cards.extend(self.cards.iter().cloned());
+                    best_five(&cards)
+                }
+                GameVariant::OMAHA => best_omaha(&player.cards, &self.cards),
+                GameVariant::SEVEN_CARD_STUD => {
+                    let mut cards = player.cards.clone();
+                    cards.extend(player.up_cards.iter().cloned());
+                    best_five(&cards)
+                }
+            };
+            match best_hand {
+                Some((rank, best)) => self.hands.push(ShowdownHand {
+                    addr: player.addr.to_string(),
+                    rank,
+                    cards: best,
+                }),
+                None => {
+                    return Err(ContractError::new(
+                        "Not enough cards to evaluate the hand.",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //Gives the player with the largest bet back the part of it that nobody matched,
+    //so that it is not raked.
+    fn return_uncalled_bet(&mut self) {
+        let top = self.contributions
+            .iter()
+            .max_by_key(|&(_, amount)| *amount)
+            .map(|(addr, amount)| (addr.clone(), *amount));
+        let (addr, amount) = match top {
+            Some(top) => top,
+            None => return,
+        };
+        let called = self.contributions
+            .iter()
+            .filter(|&(other, _)| *other != addr)
+            .map(|(_, amount)| *amount)
+            .max()
+            .unwrap_or(0);
+        let seat = match self.index.get(&addr) {
+            Some(seat) if *seat > -1 => *seat as usize,
+            _ => return,
+        };
+        if amount > called {
+            self.seats[seat].balance += amount - called;
+            self.contributions.insert(addr, called);
+            self.build_pots();
+        }
+    }
+
+    //Takes the house's share of a pot, given how much has already been raked from this
+    //hand. Hands that end before the flop, or on third street in stud, are not raked
+    //with no flop no drop.
+    fn take_rake(&mut self, pot: u64, raked: u64) -> u64 {
+        let no_flop = match self.variant {
+            GameVariant::SEVEN_CARD_STUD => self.stage == GameStage::ThirdStreet,
+            _ => self.cards.is_empty(),
+        };
+        if self.no_flop_no_drop && no_flop {
+            return 0;
+        }
+        let mut rake = pot * self.rake_basis_points / 10000;
+        if self.rake_cap > 0 {
+            rake = min(rake, self.rake_cap.saturating_sub(raked));
+        }
+        self.house_balance += rake;
+        rake
+    }
+
+    //Awards each pot, starting with the main pot, to the best hands among the
+    //players eligible for it, after the rake.
+    fn pay_winners(&mut self) -> Result<()> {
+        self.evaluate_hands()?;
+        self.return_uncalled_bet();
+        let pots = self.pots.clone();
+        let mut raked = 0;
+        for pot in pots.iter() {
+            //A pot only one player can win was never contested, so it is not raked
+            let rake = if pot.eligible.len() > 1 {
+                self.take_rake(pot.amount, raked)
+            } else {
+                0
+            };
+            raked += rake;
+            let amount = pot.amount - rake;
+            let mut winners: Vec<usize> = Vec::new();
+            for (i, hand) in self.hands.iter().enumerate() {
+                if !pot.eligible.contains(&hand.addr) {
+                    continue;
+                }
+                if winners.is_empty() || hand.rank > self.hands[winners[0]].rank {
+                    winners.clear();
+                    winners.push(i);
+                } else if hand.rank == self.hands[winners[0]].rank {
+                    winners.push(i);
+                }
+            }
+            //The odd chips of a split pot go to the first winner clockwise from the button.
+            let winners: Vec<usize> = winners
+                .iter()
+                .map(|w| self.index[&self.hands[*w].addr] as usize)
+                .collect();
+            let share = amount / winners.len() as u64;
+            let remainder = amount % winners.len() as u64;
+            let mut first = winners[0];
+            for i in winners {
+                self.seats[i].balance += share;
+                if self.seats_from_button(i) < self.seats_from_button(first) {
+                    first = i;
+                }
+            }
+            self.seats[first].balance += remainder;
+        }
+        self.pots.clear();
+        self.publish_history();
+        if self.is_tournament() {
+            self.eliminate_players();
+        }
+        Ok(())
+    }
+
+    //Whether the table is running a tournament rather than a cash game.
+    fn is_tournament(&self) -> bool {
+        !self.tournament.get_levels().is_empty()
+    }
+
+    //Adds a rebuy or add-on to the prize pool. The coordinator of a multi-table
+    //tournament keeps the prize pool for all of its tables.
+    fn add_to_prize_pool(&mut self, deposit: u64) {
+        if self.coordinator == 0 {
+            self.prize_pool += deposit;
+        }
+    }
+
+    //Whether the tournament is still in its rebuy period.
+    fn rebuys_open(&self) -> bool {
+        self.level < self.tournament.get_rebuy_levels()
+    }
+
+    //Checks that a rebuy or add-on can be sold for `deposit`. They are only sold at
+    //tournaments, between hands once play has started.
+    fn check_purchase(&self, deposit: u64) -> Result<()> {
+        if !self.is_tournament() {
+            return Err(ContractError::new(
+                "Rebuys and add-ons are only sold at tournaments.",
+            ));
+        }
+        if self.tournament_over {
+            return Err(ContractError::new("This tournament is over."));
+        }
+        if self.hand_number == 0 || !self.between_hands() {
+            return Err(ContractError::new(
+                "Rebuys and add-ons are only sold between hands once play has started.",
+            ));
+        }
+        if deposit != self.tournament.get_buy_in() {
+            return Err(ContractError::new(
+                "The deposit must be the tournament buy-in.",
+            ));
+        }
+        Ok(())
+    }
+
+    //Moves the tournament on to the blind level due by now. A new level only takes
+    //effect from the next hand dealt. The blinds never go back down, as the coordinator
+    //may have raised them to the level reached at another table.
+    fn update_blind_level(&mut self) {
+        let elapsed = self.clock.now().saturating_sub(self.started_at);
+        let level = current_level(&self.tournament, self.hand_number, elapsed) as u64;
+        let level = max(level, self.level);
+        self.set_level(level);
+    }
+
+    fn set_level(&mut self, level: u64) {
+        let blinds = self.tournament.get_levels()[level as usize].clone();
+        self.level = level;
+        self.blind = blinds.get_blind();
+        self.small_blind_amount = match blinds.get_small_blind_amount() {
+            0 => blinds.get_blind() / 2,
+            amount => amount,
+        };
+        self.ante = blinds.get_ante();
+    }
+
+    //Knocks out tournament players who have run out of chips, recording where they
+    //finished and what they won. Players knocked out in the same hand are placed by
+    //the chips they started it with. Once one player is left, he or she wins the rest
+    //of the prize pool and the tournament is over.
+    //Tables in a multi-table tournament leave placing to the coordinator, which can
+    //see every table, and record busted players with no place.
+    //While rebuys are open, busted players wait on deck to buy back in instead, and
+    //once rebuys close, those who did not are knocked out first.
+    fn eliminate_players(&mut self) {
+        let with_chips = self.seats
+            .iter()
+            .filter(|player| player.status != SeatStatus::Empty && player.balance > 0)
+            .count();
+        //With a single player left with chips, nobody can wait for a rebuy any longer.
+        //At a multi-table tournament players can still be moved in from other tables.
+        if self.rebuys_open() && (with_chips > 1 || self.coordinator != 0) {
+            return;
+        }
+        while let Some(i) = self.on_deck.iter().position(|player| player.balance == 0) {
+            let place = self.players_remaining();
+            let player = self.on_deck.remove(i);
+            self.record_finish(player.addr.to_string(), place);
+        }
+        let mut busted: Vec<usize> = (0..self.seats.len())
+            .filter(|i| {
+                self.seats[*i].status != SeatStatus::Empty && self.seats[*i].balance == 0
+            })
+            .collect();
+        busted.sort_by_key(|i| self.committed(&self.seats[*i].addr));
+        for i in busted {
+            let place = if self.coordinator != 0 {
+                0
+            } else {
+                self.players_remaining()
+            };
+            let player = mem::replace(&mut self.seats[i], Player::empty());
+            self.record_finish(player.addr.to_string(), place);
+        }
+        self.reindex();
+        if self.coordinator != 0 {
+            return;
+        }
+
+        if self.players_remaining() == 1 {
+            let winner = self.seats
+                .iter()
+                .find(|player| player.status != SeatStatus::Empty)
+                .map(|player| player.addr.to_string())
+                .unwrap_or_default();
+            self.record_finish(winner, 1);
+            self.tournament_over = true;
+        }
+    }
+
+    //Number of tournament players who have not been knocked out, counting busted
+    //players waiting on deck to rebuy.
+    pub fn players_remaining(&self) -> u64 {
+        let seated = self.seats
+            .iter()
+            .filter(|player| player.status != SeatStatus::Empty)
+            .count();
+        (seated + self.on_deck.len()) as u64
+    }
+
+    //Records where a player finished and the prize he or she won.
+    fn record_finish(&mut self, addr: String, place: u64) {
+        let result = finish(&self.tournament, self.prize_pool, &self.results, addr, place);
+        self.results.push(result);
+    }
+
+    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+    // FUNCTIONS USED BY THE TOURNAMENT COORDINATOR
+    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+
+    //Makes the table one of the tables of multi-table tournament `tournament_id`,
+    //which started at `started_at`. Every table counts the time spent on each blind
+    //level from the same start.
+    pub fn set_coordinator(&mut self, tournament_id: u64, started_at: u64) {
+        self.coordinator = tournament_id;
+        self.started_at = started_at;
+    }
+
+    pub fn get_coordinator(&self) -> u64 {
+        self.coordinator
+    }
+
+    pub fn get_level(&self) -> u64 {
+        self.level
+    }
+
+    //Raises the blinds between hands to `level` if the table is not there yet, so that
+    //a table playing fewer hands than the others does not fall behind.
+    pub fn raise_level(&mut self, level: u64) {
+        if self.between_hands() && level > self.level {
+            self.set_level(level);
+        }
+    }
+
+    //Whether no hand is in progress, so players can be moved to or from the table.
+    pub fn between_hands(&self) -> bool {
+        match self.stage {
+            GameStage::Join | GameStage::Showdown => true,
+            _ => false,
+        }
+    }
+
+    //Seats a player moved from another table, or a new entrant, in the first empty
+    //seat. The player keeps his or her stack and any blinds he or she owes. Once the
+    //table has started, he or she is only dealt in when the big blind reaches him or
+    //her, so that changing tables cannot be used to skip the blinds.
+    pub fn seat_player(&mut self, mut player: Player) -> Result<()> {
+        let seat = match self.empty_seat() {
+            Some(seat) => seat,
+            None => return Err(ContractError::new("The table is full.")),
+        };
+        player.status = SeatStatus::SittingOut;
+        player.action = QueuedAction::None;
+        player.wait_for_big_blind = self.big_blind > -1;
+        self.total_chips += player.balance;
+        self.seats[seat] = player;
+        self.reindex();
+        Ok(())
+    }
+
+    //Takes the player in `seat` away from the table between hands, together with his
+    //or her chips.
+    pub fn unseat_player(&mut self, seat: usize) -> Result<Player> {
+        if !self.between_hands() {
+            return Err(ContractError::new(
+                "Players cannot be moved while a hand is being played.",
+            ));
+        }
+        if self.seats[seat].status == SeatStatus::Empty {
+            return Err(ContractError::new("This seat is empty."));
+        }
+        let player = mem::replace(&mut self.seats[seat], Player::empty());
+        self.reindex();
+        self.total_chips -= player.balance;
+        Ok(player)
+    }
+
+    //Seat of the player due to post the next big blind. He or she is the one moved
+    //when balancing tables, so that nobody skips the blinds or pays them twice. If
+    //every player is sitting out, the first seated player is moved instead.
+    pub fn next_big_blind_seat(&self) -> usize {
+        let seat = self.next_ready(self.big_blind) as usize;
+        if self.seats[seat].status != SeatStatus::Empty {
+            return seat;
+        }
+        self.seats
+            .iter()
+            .position(|player| player.status != SeatStatus::Empty)
+            .unwrap_or(seat)
+    }
+
+    //Addresses of the players still seated at the table.
+    pub fn seated_players(&self) -> Vec<String> {
+        self.seats
+            .iter()
+            .filter(|player| player.status != SeatStatus::Empty)
+            .map(|player| player.addr.to_string())
+            .collect()
+    }
+
+    //Players knocked out at the table since the coordinator last asked, in the order
+    //they went out.
+    pub fn take_unplaced(&mut self) -> Vec<String> {
+        let busted = self.results
+            .iter()
+            .filter(|result| result.get_place() == 0)
+            .map(|result| result.get_addr().to_string())
+            .collect();
+        self.results.retain(|result| result.get_place() != 0);
+        busted
+    }
+
+    //Stops play once the coordinator has found the winner.
+    pub fn end_tournament(&mut self) {
+        self.tournament_over = true;
+    }
+
+    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+    // FUNCTIONS TO REQUEST AND FORMAT STATE
+    //+++++++++++++++++++++++++++++++++++++++++++++++++++++
+
+    //Actions `msg_sender` may take right now, with the range of amounts allowed for
+    //each as the player's total bet once the action is made. Empty unless it is his
+    //or her turn to bet.
+    pub fn get_legal_actions(&self, msg_sender: &Address) -> Vec<LegalAction> {
+        let mut actions = Vec::new();
+        if !self.stage.is_betting_round() {
+            return actions;
+        }
+        let i = match self.get_index(msg_sender) {
+            Ok(i) if i == self.next_player => i as usize,
+            _ => return actions,
+        };
+        let player = &self.seats[i];
+        let legal = |action: Action, min_amount: u64, max_amount: u64| {
+            let mut legal_action = LegalAction::new();
+            legal_action.set_action(action.to_proto());
+            legal_action.set_min_amount(min_amount);
+            legal_action.set_max_amount(max_amount);
+            legal_action
+        };
+
+        actions.push(legal(Action::Fold, 0, 0));
+        let owed = self.min_bet - player.bet;
+        if owed == 0 {
+            actions.push(legal(Action::Check, 0, 0));
+        } else if owed <= player.balance {
+            actions.push(legal(Action::Call, self.min_bet, self.min_bet));
+        }
+        if let Some((least, most)) = self.raise_limits(i) {
+            let most = min(most, player.bet + player.balance);
+            let action = if self.min_bet == 0 { Action::Bet } else { Action::Raise };
+            if most >= least {
+                actions.push(legal(action, least, most));
+            }
+        }
+        if player.balance > 0 && self.can_go_all_in(i) {
+            let all_in = player.bet + player.balance;
+            actions.push(legal(Action::AllIn, all_in, all_in));
+        }
+        actions
+    }
+
+    //State of the table as seen by `msg_sender`. Hole cards and seeds are hidden,
+    //except that the hands still in at showdown are turned face up.
+    pub fn get_public_state(&self, msg_sender: &Address) -> Result<PublicState> {
+        let mut state = PublicState::new();
+
+        state.set_game_id(self.game_id);
+        state.set_blind(self.blind);
+        state.set_small_blind_amount(self.small_blind_amount);
+        state.set_ante(self.ante);
+        state.set_big_blind_ante(self.big_blind_ante);
+        state.set_straddle(self.straddle);
+        state.set_max_players(self.max_players);
+        state.set_time_per_turn(self.time_per_turn);
+        //Only the hands still in at showdown are turned face up
+        let showdown = self.stage == GameStage::Showdown;
+        let players: Vec<PlayerState> = self.seats
+            .iter()
+            .map(|player| player.to_state(showdown && player.status.in_hand()))
+            .collect();
+        state.set_players(players);
+        state.set_on_deck(self.serialize_players(&self.on_deck, false));
+        state.set_pot(self.total_pot());
+        state.set_pots(self.serialize_pots());
+        state.set_min_bet(self.min_bet);
+        state.set_dealer(self.dealer);
+        state.set_small_blind(self.small_blind);
+        state.set_big_blind(self.big_blind);
+        state.set_next_player(self.next_player);
+        state.set_turn_deadline(self.turn_deadline);
+        state.set_last_player(self.last_player);
+        state.set_game_stage(self.stage.to_string());
+        state.set_hands(self.serialize_hands());
+        state.set_seed_commitment(self.pending_history.get_seed_commitment().to_vec());
+        state.set_history(self.public_history());
+        state.set_cards(serde_cbor::to_vec(&self.cards).expect("Unable to serialize cards."));
+        if self.stage.is_betting_round() {
+            let turn = &self.seats[self.next_player as usize];
+            state.set_turn(turn.addr.to_string());
+            state.set_to_call(self.min_bet - turn.bet);
+            if let Some((least, _)) = self.raise_limits(self.next_player as usize) {
+                state.set_min_raise(least);
+            }
+        }
+        state.set_variant(self.variant);
+        state.set_betting_structure(self.betting_structure);
+        state.set_rake_basis_points(self.rake_basis_points);
+        state.set_rake_cap(self.rake_cap);
+        state.set_no_flop_no_drop(self.no_flop_no_drop);
+        state.set_tournament(self.tournament.clone());
+        state.set_level(self.level);
+        state.set_prize_pool(self.prize_pool);
+        state.set_results(self.results.clone());
+        state.set_tournament_over(self.tournament_over);
+        state.set_coordinator(self.coordinator);
+        state.set_min_buy_in(self.min_buy_in);
+        state.set_max_buy_in(self.max_buy_in);
+        state.set_hand_number(self.hand_number);
+        state.set_legal_actions(self.get_legal_actions(msg_sender));
+
+        Ok(state)
+    }
+
+    //Parameters and occupancy of the table, as shown by `list_games`.
+    pub fn get_summary(&self) -> GameSummary {
+        let mut summary = GameSummary::new();
+        summary.set_game_id(self.game_id);
+        summary.set_blind(self.blind);
+        summary.set_small_blind_amount(self.small_blind_amount);
+        summary.set_ante(self.ante);
+        summary.set_max_players(self.max_players);
+        summary.set_time_per_turn(self.time_per_turn);
+        summary.set_variant(self.variant);
+        summary.set_betting_structure(self.betting_structure);
+        summary.set_rake_basis_points(self.rake_basis_points);
+        summary.set_rake_cap(self.rake_cap);
+        summary.set_buy_in(self.tournament.get_buy_in());
+        summary.set_min_buy_in(self.min_buy_in);
+        summary.set_max_buy_in(self.max_buy_in);
+        let seated = self.seats
+            .iter()
+            .filter(|player| player.status != SeatStatus::Empty)
+            .count();
+        summary.set_players(seated as u64);
+        summary.set_on_deck(self.on_deck.len() as u64);
+        summary.set_game_stage(self.stage.to_string());
+        summary
+    }
+
+    //Whether new players can join the table. Cash tables stay open while they run,
+    //while a tournament table only takes entrants until the first hand is dealt.
+    //Tables of a multi-table tournament are filled by the coordinator instead.
+    pub fn is_open(&self) -> bool {
+        if self.coordinator != 0 || self.tournament_over {
+            return false;
+        }
+        if self.is_tournament() {
+            return self.hand_number == 0
+                && self.stage == GameStage::Join
+                && self.empty_seat().is_some();
+        }
+        true
+    }
+
+    //State of the calling player, including his or her own hole cards.
+    //The request must be for the current hand and made less than one turn ago.
+    pub fn get_player_state(
+        &self,
+        msg_sender: &Address,
+        hand_number: u64,
+        timestamp: u64,
+    ) -> Result<PlayerState> {
+        if hand_number != self.hand_number {
+            return Err(ContractError::new("Request is for a different hand."));
+        }
+        let now = self.clock.now();
+        if timestamp > now || now - timestamp > self.time_per_turn {
+            return Err(ContractError::new("Player state request has expired."));
+        }
+        for player in self.seats.iter().chain(self.on_deck.iter()) {
+            if *msg_sender == player.addr {
+                let mut state = player.to_state(true);
+                state.set_nonce(self.get_nonce(msg_sender));
+                let dealt_in = self.history
+                    .get_reveals()
+                    .iter()
+                    .any(|reveal| reveal.get_addr() == msg_sender.to_string());
+                if dealt_in {
+                    state.set_history(self.history.clone());
+                }
+                return Ok(state);
+            }
+        }
+        Err(ContractError::new("This player has not joined the game."))
+    }
+
+    //The last hand's history without the shuffle inputs, the deck and the order it
+    //was dealt in, which would give away the hands that were not shown.
+    fn public_history(&self) -> HandHistory {
+        let mut history = HandHistory::new();
+        history.set_hand_number(self.history.get_hand_number());
+        history.set_seed_commitment(self.history.get_seed_commitment().to_vec());
+        history.set_board(self.history.get_board().to_vec());
+        history.set_variant(self.history.get_variant());
+        history.set_shown(self.history.get_shown().to_vec());
+        history
+    }
+
+    fn serialize_players(&self, players: &Vec<Player>, show_private: bool) -> Vec<PlayerState> {
+        let mut formatted_players: Vec<PlayerState> = Vec::new();
+        for player in players {
+            formatted_players.push(player.to_state(show_private));
+        }
+        return formatted_players;
+    }
+
+    fn serialize_pots(&self) -> Vec<PotState> {
+        let mut formatted_pots: Vec<PotState> = Vec::new();
+        for pot in self.pots.iter() {
+            let mut state = PotState::new();
+            state.set_amount(pot.amount);
+            state.set_eligible(pot.eligible.clone());
+            formatted_pots.push(state);
+        }
+        return formatted_pots;
+    }
+
+    fn serialize_hands(&self) -> Vec<HandResult> {
+        self.hands.iter().map(|hand| hand.to_state()).collect()
+    }
+
+    fn serialize_contributions(&self, contributions: &HashMap<String, u64>) -> Vec<Contribution> {
+        let mut formatted_contributions: Vec<Contribution> = Vec::new();
+        for (addr, amount) in contributions.iter() {
+            let mut contribution = Contribution::new();
+            contribution.set_addr(addr.clone());
+            contribution.set_amount(*amount);
+            formatted_contributions.push(contribution);
+        }
+        return formatted_contributions;
+    }
+}
+
+impl Contract<PokerState> for PokerContract {
+    /// Get serializable contract state.
     fn get_state(&self) -> PokerState {
         let mut state = PokerState::new();
         state.set_game_id(self.game_id);
         state.set_blind(self.blind);
+        state.set_small_blind_amount(self.small_blind_amount);
+        state.set_ante(self.ante);
+        state.set_big_blind_ante(self.big_blind_ante);
+        state.set_straddle(self.straddle);
         state.set_max_players(self.max_players);
-        state.set_players(self.serialize_players(&self.players));
-        state.set_on_deck(self.serialize_players(&self.on_deck));
+        state.set_time_per_turn(self.time_per_turn);
+        state.set_variant(self.variant);
+        state.set_betting_structure(self.betting_structure);
+        state.set_raise_cap(self.raise_cap);
+        state.set_rake_basis_points(self.rake_basis_points);
+        state.set_rake_cap(self.rake_cap);
+        state.set_no_flop_no_drop(self.no_flop_no_drop);
+        state.set_operator(self.operator.clone());
+        state.set_min_buy_in(self.min_buy_in);
+        state.set_max_buy_in(self.max_buy_in);
+        state.set_house_balance(self.house_balance);
+        state.set_tournament(self.tournament.clone());
+        state.set_level(self.level);
+        state.set_started_at(self.started_at);
+        state.set_prize_pool(self.prize_pool);
+        state.set_entrants(self.entrants);
+        state.set_results(self.results.clone());
+        state.set_tournament_over(self.tournament_over);
+        state.set_coordinator(self.coordinator);
+        state.set_players(self.serialize_players(&self.seats, true));
+        state.set_on_deck(self.serialize_players(&self.on_deck, true));
         state.set_cards(serde_cbor::to_vec(&self.cards).expect("Unable to serialze cards."));
         state.set_deck(serde_cbor::to_vec(&self.deck).expect("Unable to serialze deck."));
-        state.set_pot(self.pot);
+        state.set_pot(self.total_pot());
+        state.set_pots(self.serialize_pots());
+        state.set_contributions(self.serialize_contributions(&self.contributions));
+        state.set_dead_money(self.serialize_contributions(&self.dead_money));
+        state.set_hands(self.serialize_hands());
+        state.set_total_chips(self.total_chips);
+        state.set_nonces(serialize_nonces(&self.nonces));
         state.set_min_bet(self.min_bet);
+        state.set_last_raise(self.last_raise);
+        state.set_raises(self.raises);
+        state.set_acted(self.acted.clone());
         state.set_dealer(self.dealer);
+        state.set_small_blind(self.small_blind);
+        state.set_big_blind(self.big_blind);
         state.set_next_player(self.next_player);
         state.set_last_player(self.last_player);
-        state.set_stage(self.stage.to_string());
-        state.set_seed(self.seed.clone());
+        state.set_turn_deadline(self.turn_deadline);
+        state.set_game_stage(self.stage.to_string());
+        state.set_hand_number(self.hand_number);
+        state.set_pending_history(self.pending_history.clone());
+        state.set_history(self.history.clone());
+        state.set_seed(self.seed.to_vec());
 
         state
     }
 
     /// Create contract instance from serialized state.
     fn from_state(state: &PokerState) -> PokerContract {
-        PokerContract {
-            game_id: state.get_name(),
+        let mut contract = PokerContract {
+            game_id: state.get_game_id(),
             blind: state.get_blind(),
+            small_blind_amount: state.get_small_blind_amount(),
+            ante: state.get_ante(),
+            big_blind_ante: state.get_big_blind_ante(),
+            straddle: state.get_straddle(),
             max_players: state.get_max_players(),
             time_per_turn: state.get_time_per_turn(),
-            players: state.get_players().clone(),
-            on_deck: state.get_on_deck().clone(),
+            variant: state.get_variant(),
+            betting_structure: state.get_betting_structure(),
+            raise_cap: state.get_raise_cap(),
+            rake_basis_points: state.get_rake_basis_points(),
+            rake_cap: state.get_rake_cap(),
+            no_flop_no_drop: state.get_no_flop_no_drop(),
+            operator: state.get_operator().to_string(),
+            min_buy_in: state.get_min_buy_in(),
+            max_buy_in: state.get_max_buy_in(),
+            house_balance: state.get_house_balance(),
+            tournament: state.get_tournament().clone(),
+            level: state.get_level(),
+            started_at: state.get_started_at(),
+            prize_pool: state.get_prize_pool(),
+            entrants: state.get_entrants(),
+            results: state.get_results().to_vec(),
+            tournament_over: state.get_tournament_over(),
+            coordinator: state.get_coordinator(),
+            seats: state.get_players().iter().map(Player::from_state).collect(),
+            on_deck: state.get_on_deck().iter().map(Player::from_state).collect(),
             cards: serde_cbor::from_slice(state.get_cards()).expect("Unable to deserialize cards"),
             deck: serde_cbor::from_slice(state.get_deck()).expect("Unable to deserialize deck"),
-            pot: state.get_pot(),
+            pots: state.get_pots().iter().map(Pot::from_state).collect(),
+            contributions: state
+                .get_contributions()
+                .iter()
+                .map(|c| (c.get_addr().to_string(), c.get_amount()))
+                .collect(),
+            dead_money: state
+                .get_dead_money()
+                .iter()
+                .map(|c| (c.get_addr().to_string(), c.get_amount()))
+                .collect(),
+            hands: state.get_hands().iter().map(ShowdownHand::from_state).collect(),
+            total_chips: state.get_total_chips(),
+            nonces: state
+                .get_nonces()
+                .iter()
+                .map(|n| (n.get_addr().to_string(), n.get_nonce()))
+                .collect(),
+            index: HashMap::new(),
             min_bet: state.get_min_bet(),
+            last_raise: state.get_last_raise(),
+            raises: state.get_raises(),
+            acted: state.get_acted().to_vec(),
             dealer: state.get_dealer(),
+            small_blind: state.get_small_blind(),
+            big_blind: state.get_big_blind(),
             next_player: state.get_next_player(),
             last_player: state.get_last_player(),
-            stage: GameStage::from_string(state.get_stage()),
-            seed: state.get_seed().clone(),
-        }
+            turn_deadline: state.get_turn_deadline(),
+            stage: GameStage::from_string(state.get_game_stage()),
+            hand_number: state.get_hand_number(),
+            pending_history: state.get_pending_history().clone(),
+            history: state.get_history().clone(),
+            seed: {
+                let mut seed = [0; 32];
+                seed.copy_from_slice(state.get_seed());
+                seed
+            },
+            clock: Box::new(EnclaveClock),
+        };
+        contract.reindex();
+        contract
     }
 }
 
@@ -397,30 +2503,245 @@ impl<'a> Contract<PokerState> for PokerContract<'a> {
 
 pub struct Player {
     addr: Address,
+    //Hole cards, or the cards dealt face down in stud
     cards: Vec<Card>,
-    action: Action,
-    playing: bool,
+    //Stud cards dealt face up
+    up_cards: Vec<Card>,
+    //Action to take automatically when the turn reaches the player
+    action: QueuedAction,
+    status: SeatStatus,
+    //Whether the player has asked to be left out of the coming hands
+    sitting_out: bool,
+    //Whether the player is waiting for the big blind to reach his or her seat
+    wait_for_big_blind: bool,
+    //Blinds the player owes for hands he or she was not dealt into
+    missed_small_blind: bool,
+    missed_big_blind: bool,
+    //Tournament rebuys and add-on bought so far
+    rebuys: u64,
+    added_on: bool,
     bet: u64,
     balance: u64,
+    //SHA-256 hash of the seed the player will reveal for this hand
+    commitment: Vec<u8>,
+    //The revealed seed, empty until the player reveals it
+    reveal: Vec<u8>,
+}
+
+impl Player {
+    //A player arriving at the table with `balance` chips. He or she sits out until
+    //dealt into the next hand.
+    pub fn new(addr: Address, balance: u64) -> Player {
+        Player {
+            addr,
+            cards: Vec::new(),
+            up_cards: Vec::new(),
+            action: QueuedAction::None,
+            status: SeatStatus::SittingOut,
+            sitting_out: false,
+            wait_for_big_blind: false,
+            missed_small_blind: false,
+            missed_big_blind: false,
+            rebuys: 0,
+            added_on: false,
+            bet: 0,
+            balance,
+            commitment: Vec::new(),
+            reveal: Vec::new(),
+        }
+    }
+
+    //Placeholder for an empty seat.
+    fn empty() -> Player {
+        Player {
+            addr: Address::from(String::new()),
+            cards: Vec::new(),
+            up_cards: Vec::new(),
+            action: QueuedAction::None,
+            status: SeatStatus::Empty,
+            sitting_out: false,
+            wait_for_big_blind: false,
+            missed_small_blind: false,
+            missed_big_blind: false,
+            rebuys: 0,
+            added_on: false,
+            bet: 0,
+            balance: 0,
+            commitment: Vec::new(),
+            reveal: Vec::new(),
+        }
+    }
+
+    //Serializes the player. Hole cards and the revealed seed are left out unless
+    //`show_private` is set. Stud up cards are always shown.
+    fn to_state(&self, show_private: bool) -> PlayerState {
+        let mut state = PlayerState::new();
+
+        state.set_addr(self.addr.to_string());
+        if show_private {
+            state.set_action(self.action.to_string());
+            state.set_cards(serde_cbor::to_vec(&self.cards).expect("Unable to serialize cards."));
+            state.set_reveal(self.reveal.clone());
+        }
+        state.set_up_cards(serde_cbor::to_vec(&self.up_cards).expect("Unable to serialize cards."));
+        state.set_status(self.status.to_string());
+        state.set_sitting_out(self.sitting_out);
+        state.set_wait_for_big_blind(self.wait_for_big_blind);
+        state.set_missed_small_blind(self.missed_small_blind);
+        state.set_missed_big_blind(self.missed_big_blind);
+        state.set_rebuys(self.rebuys);
+        state.set_added_on(self.added_on);
+        state.set_bet(self.bet);
+        state.set_balance(self.balance);
+        state.set_commitment(self.commitment.clone());
+
+        state
+    }
+
+    fn from_state(state: &PlayerState) -> Player {
+        Player {
+            addr: Address::from(state.get_addr().to_string()),
+            cards: serde_cbor::from_slice(state.get_cards()).expect("Unable to deserialize cards"),
+            up_cards: serde_cbor::from_slice(state.get_up_cards())
+                .expect("Unable to deserialize cards"),
+            action: QueuedAction::from_string(state.get_action()),
+            status: SeatStatus::from_string(state.get_status()),
+            sitting_out: state.get_sitting_out(),
+            wait_for_big_blind: state.get_wait_for_big_blind(),
+            missed_small_blind: state.get_missed_small_blind(),
+            missed_big_blind: state.get_missed_big_blind(),
+            rebuys: state.get_rebuys(),
+            added_on: state.get_added_on(),
+            bet: state.get_bet(),
+            balance: state.get_balance(),
+            commitment: state.get_commitment().to_vec(),
+            reveal: state.get_reveal().to_vec(),
+        }
+    }
+}
+
+//A main pot or side pot, together with the addresses of the players who can win it.
+#[derive(Clone)]
+pub struct Pot {
+    amount: u64,
+    eligible: Vec<String>,
+}
+
+impl Pot {
+    fn from_state(state: &PotState) -> Pot {
+        Pot {
+            amount: state.get_amount(),
+            eligible: state.get_eligible().to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SeatStatus {
+    Empty,
+    //Dealt into the hand and still able to bet
+    Active,
+    Folded,
+    AllIn,
+    //Seated but not dealt into the hand
+    SittingOut,
+}
+
+impl SeatStatus {
+    //Whether the player in the seat can still win the hand.
+    fn in_hand(&self) -> bool {
+        match *self {
+            SeatStatus::Active | SeatStatus::AllIn => true,
+            _ => false,
+        }
+    }
+
+    fn to_string(&self) -> String {
+        match *self {
+            SeatStatus::Empty => "Empty",
+            SeatStatus::Active => "Active",
+            SeatStatus::Folded => "Folded",
+            SeatStatus::AllIn => "AllIn",
+            SeatStatus::SittingOut => "SittingOut",
+        }.to_string()
+    }
+
+    fn from_string(string: &str) -> SeatStatus {
+        match string {
+            "Active" => SeatStatus::Active,
+            "Folded" => SeatStatus::Folded,
+            "AllIn" => SeatStatus::AllIn,
+            "SittingOut" => SeatStatus::SittingOut,
+            _ => SeatStatus::Empty,
+        }
+    }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 enum GameStage {
     Join,
-    Play,
+    Commit,
+    Reveal,
+    PreFlop,
+    Flop,
+    Turn,
+    River,
+    //Betting rounds of seven-card stud
+    ThirdStreet,
+    FourthStreet,
+    FifthStreet,
+    SixthStreet,
+    SeventhStreet,
+    Showdown,
 }
 
 impl GameStage {
-    fn to_string(&self) -> String {
-        match self {
-            GameStage::Join => return "Join",
-            GameStage::Play => return "Play",
+    //Whether players are currently allowed to bet.
+    fn is_betting_round(&self) -> bool {
+        match *self {
+            GameStage::PreFlop | GameStage::Flop | GameStage::Turn | GameStage::River => true,
+            GameStage::ThirdStreet
+            | GameStage::FourthStreet
+            | GameStage::FifthStreet
+            | GameStage::SixthStreet
+            | GameStage::SeventhStreet => true,
+            _ => false,
         }
     }
 
-    fn from_string(string: &String) -> GameStage {
+    fn to_string(&self) -> String {
+        match *self {
+            GameStage::Join => "Join",
+            GameStage::Commit => "Commit",
+            GameStage::Reveal => "Reveal",
+            GameStage::PreFlop => "PreFlop",
+            GameStage::Flop => "Flop",
+            GameStage::Turn => "Turn",
+            GameStage::River => "River",
+            GameStage::ThirdStreet => "ThirdStreet",
+            GameStage::FourthStreet => "FourthStreet",
+            GameStage::FifthStreet => "FifthStreet",
+            GameStage::SixthStreet => "SixthStreet",
+            GameStage::SeventhStreet => "SeventhStreet",
+            GameStage::Showdown => "Showdown",
+        }.to_string()
+    }
+
+    fn from_string(string: &str) -> GameStage {
         match string {
-            "Join" => GameStage::Join,
-            "Play" => GameStage::Play,
+            "Commit" => GameStage::Commit,
+            "Reveal" => GameStage::Reveal,
+            "PreFlop" => GameStage::PreFlop,
+            "Flop" => GameStage::Flop,
+            "Turn" => GameStage::Turn,
+            "River" => GameStage::River,
+            "ThirdStreet" => GameStage::ThirdStreet,
+            "FourthStreet" => GameStage::FourthStreet,
+            "FifthStreet" => GameStage::FifthStreet,
+            "SixthStreet" => GameStage::SixthStreet,
+            "SeventhStreet" => GameStage::SeventhStreet,
+            "Showdown" => GameStage::Showdown,
+            _ => GameStage::Join,
         }
     }
 }
@@ -428,29 +2749,646 @@ impl GameStage {
 pub enum Action {
     None,
     Check,
-    Match,
+    Bet,
+    Call,
     Raise,
+    AllIn,
     Fold,
 }
 
 impl Action {
-    fn to_string(&self) -> String {
-        match self {
-            Action::None => "None",
-            Action::Check => "Check",
-            Action::Match => "Match",
-            Action::Bet => "Bet",
-            Action::Fold => "Fold",
+    pub fn from_proto(action: ActionType) -> Action {
+        match action {
+            ActionType::NONE => Action::None,
+            ActionType::CHECK => Action::Check,
+            ActionType::BET => Action::Bet,
+            ActionType::CALL => Action::Call,
+            ActionType::RAISE => Action::Raise,
+            ActionType::ALL_IN => Action::AllIn,
+            ActionType::FOLD => Action::Fold,
+        }
+    }
+
+    fn to_proto(&self) -> ActionType {
+        match *self {
+            Action::None => ActionType::NONE,
+            Action::Check => ActionType::CHECK,
+            Action::Bet => ActionType::BET,
+            Action::Call => ActionType::CALL,
+            Action::Raise => ActionType::RAISE,
+            Action::AllIn => ActionType::ALL_IN,
+            Action::Fold => ActionType::FOLD,
         }
     }
+}
+
+//An action stored by a player to be taken when the turn reaches him or her.
+#[derive(Clone, Copy)]
+pub enum QueuedAction {
+    None,
+    CheckFold,
+    CallAny,
+    FoldToBet,
+}
+
+impl QueuedAction {
+    fn to_string(&self) -> String {
+        match *self {
+            QueuedAction::None => "None",
+            QueuedAction::CheckFold => "CheckFold",
+            QueuedAction::CallAny => "CallAny",
+            QueuedAction::FoldToBet => "FoldToBet",
+        }.to_string()
+    }
 
-    fn from_string(string: &String) -> Action {
+    fn from_string(string: &str) -> QueuedAction {
         match string {
-            "None" => Action::None,
-            "Check" => Action::Check,
-            "Match" => Action::Match,
-            "Bet" => Action::Bet,
-            "Fold" => Action::Fold,
+            "CheckFold" => QueuedAction::CheckFold,
+            "CallAny" => QueuedAction::CallAny,
+            "FoldToBet" => QueuedAction::FoldToBet,
+            _ => QueuedAction::None,
+        }
+    }
+
+    pub fn from_proto(action: QueuedActionType) -> QueuedAction {
+        match action {
+            QueuedActionType::CLEAR => QueuedAction::None,
+            QueuedActionType::CHECK_FOLD => QueuedAction::CheckFold,
+            QueuedActionType::CALL_ANY => QueuedAction::CallAny,
+            QueuedActionType::FOLD_TO_BET => QueuedAction::FoldToBet,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::MockClock;
+    use rs_poker::core::{Suit, Value};
+    use poker_api::BlindLevel;
+
+    fn addr(i: usize) -> Address {
+        Address::from(format!("player{}", i))
+    }
+
+    //A cash table with a big blind of 10 and `players` players holding 100 chips
+    //each, all dealt into the hand.
+    fn table(players: usize) -> PokerContract {
+        let mut options = CreateGameRequest::new();
+        options.set_blind(10);
+        options.set_max_players(6);
+        options.set_time_per_turn(30);
+        let mut contract = PokerContract::new(1, &options).unwrap();
+        for i in 0..players {
+            contract.join_game(&addr(i), 100, false).unwrap();
+        }
+        for player in contract.seats.iter_mut() {
+            if player.status != SeatStatus::Empty {
+                player.status = SeatStatus::Active;
+            }
+        }
+        contract
+    }
+
+    fn eligible(contract: &PokerContract, pot: usize) -> Vec<String> {
+        contract.pots[pot].eligible.clone()
+    }
+
+    fn card(value: Value, suit: Suit) -> Card {
+        Card { value, suit }
+    }
+
+    //Starts a betting round on the flop at time 100, with `first` to act and `last`
+    //closing the action.
+    fn flop(contract: &mut PokerContract, first: i32, last: i32) {
+        contract.set_clock(Box::new(MockClock::new(100)));
+        contract.deck = shuffle_deck(&[0; 32]);
+        contract.stage = GameStage::Flop;
+        contract.last_player = last;
+        contract.set_turn(first);
+    }
+
+    //Actions `addr` may take, without the amounts.
+    fn legal_actions(contract: &PokerContract, addr: &Address) -> Vec<ActionType> {
+        contract
+            .get_legal_actions(addr)
+            .iter()
+            .map(|action| action.get_action())
+            .collect()
+    }
+
+    //Takes 10% of every contested pot.
+    fn rake(contract: &mut PokerContract) {
+        contract.rake_basis_points = 1000;
+        contract.operator = "house".to_string();
+    }
+
+    #[test]
+    fn dead_small_blind_goes_to_the_main_pot() {
+        let mut contract = table(3);
+        contract.big_blind = 1;
+        contract.seats[0].missed_small_blind = true;
+        contract.seats[0].missed_big_blind = true;
+        contract.post_bet(1, 10);
+        contract.post_missed_blinds();
+        contract.post_bet(2, 10);
+        contract.collect_bets();
+
+        assert_eq!(contract.committed(&addr(0)), 15);
+        assert_eq!(contract.pots.len(), 1);
+        assert_eq!(contract.pots[0].amount, 35);
+        assert_eq!(eligible(&contract, 0).len(), 3);
+
+        //The player who posted dead money folding leaves the others eligible
+        contract.fold_player(0).unwrap();
+        assert_eq!(contract.pots[0].amount, 35);
+        assert_eq!(
+            eligible(&contract, 0),
+            vec![addr(1).to_string(), addr(2).to_string()]
+        );
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn big_blind_ante_goes_to_the_main_pot() {
+        let mut contract = table(3);
+        contract.ante = 10;
+        contract.big_blind_ante = true;
+        contract.big_blind = 2;
+        contract.post_antes();
+        for i in 0..3 {
+            contract.post_bet(i, 10);
+        }
+        contract.collect_bets();
+
+        assert_eq!(contract.pots.len(), 1);
+        assert_eq!(contract.pots[0].amount, 40);
+        assert_eq!(eligible(&contract, 0).len(), 3);
+
+        contract.fold_player(2).unwrap();
+        assert_eq!(contract.pots[0].amount, 40);
+        assert_eq!(
+            eligible(&contract, 0),
+            vec![addr(0).to_string(), addr(1).to_string()]
+        );
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn uncalled_bet_is_returned_before_the_rake() {
+        let mut contract = table(2);
+        rake(&mut contract);
+        contract.post_bet(0, 10);
+        contract.post_bet(1, 20);
+        contract.fold_player(0).unwrap();
+        contract.end_uncontested_hand().unwrap();
+
+        assert_eq!(contract.house_balance, 2);
+        assert_eq!(contract.seats[1].balance, 80 + 10 + 18);
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn overbet_above_an_all_in_is_returned_unraked() {
+        let mut contract = table(2);
+        rake(&mut contract);
+        contract.seats[0].balance = 50;
+        contract.total_chips -= 50;
+        contract.post_bet(0, 50);
+        contract.post_bet(1, 100);
+        contract.collect_bets();
+        contract.cards = vec![
+            card(Value::Ace, Suit::Spade),
+            card(Value::King, Suit::Spade),
+            card(Value::Queen, Suit::Spade),
+            card(Value::Jack, Suit::Spade),
+            card(Value::Two, Suit::Diamond),
+        ];
+        contract.seats[0].cards = vec![
+            card(Value::Ten, Suit::Spade),
+            card(Value::Three, Suit::Club),
+        ];
+        contract.seats[1].cards = vec![
+            card(Value::Four, Suit::Heart),
+            card(Value::Five, Suit::Heart),
+        ];
+        contract.stage = GameStage::Showdown;
+        contract.pay_winners().unwrap();
+
+        assert_eq!(contract.house_balance, 10);
+        assert_eq!(contract.seats[0].balance, 90);
+        assert_eq!(contract.seats[1].balance, 50);
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn all_in_player_only_wins_the_main_pot() {
+        let mut contract = table(3);
+        contract.seats[0].balance = 30;
+        contract.total_chips -= 70;
+        contract.post_bet(0, 30);
+        contract.post_bet(1, 60);
+        contract.post_bet(2, 60);
+        contract.collect_bets();
+
+        assert_eq!(contract.pots.len(), 2);
+        assert_eq!(contract.pots[0].amount, 90);
+        assert_eq!(eligible(&contract, 0).len(), 3);
+        assert_eq!(contract.pots[1].amount, 60);
+        assert_eq!(
+            eligible(&contract, 1),
+            vec![addr(1).to_string(), addr(2).to_string()]
+        );
+
+        contract.cards = vec![
+            card(Value::Two, Suit::Club),
+            card(Value::Seven, Suit::Diamond),
+            card(Value::Nine, Suit::Heart),
+            card(Value::Jack, Suit::Spade),
+            card(Value::King, Suit::Diamond),
+        ];
+        contract.seats[0].cards = vec![
+            card(Value::Ace, Suit::Heart),
+            card(Value::Ace, Suit::Diamond),
+        ];
+        contract.seats[1].cards = vec![
+            card(Value::King, Suit::Heart),
+            card(Value::Queen, Suit::Club),
+        ];
+        contract.seats[2].cards = vec![
+            card(Value::Three, Suit::Heart),
+            card(Value::Four, Suit::Spade),
+        ];
+        contract.stage = GameStage::Showdown;
+        contract.pay_winners().unwrap();
+
+        assert_eq!(contract.seats[0].balance, 90);
+        assert_eq!(contract.seats[1].balance, 40 + 60);
+        assert_eq!(contract.seats[2].balance, 40);
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn odd_chip_goes_to_the_first_winner_left_of_the_button() {
+        for dealer in 0..2 {
+            let mut contract = table(3);
+            contract.dealer = dealer;
+            contract.post_bet(0, 10);
+            contract.post_bet(1, 10);
+            contract.post_bet(2, 5);
+            contract.fold_player(2).unwrap();
+            contract.collect_bets();
+            assert_eq!(contract.total_pot(), 25);
+
+            //The board plays for both players
+            contract.cards = vec![
+                card(Value::Ace, Suit::Spade),
+                card(Value::King, Suit::Spade),
+                card(Value::Queen, Suit::Spade),
+                card(Value::Jack, Suit::Spade),
+                card(Value::Ten, Suit::Spade),
+            ];
+            contract.seats[0].cards = vec![
+                card(Value::Two, Suit::Club),
+                card(Value::Three, Suit::Diamond),
+            ];
+            contract.seats[1].cards = vec![
+                card(Value::Four, Suit::Club),
+                card(Value::Five, Suit::Diamond),
+            ];
+            contract.stage = GameStage::Showdown;
+            contract.pay_winners().unwrap();
+
+            let (odd, even) = if dealer == 0 { (1, 0) } else { (0, 1) };
+            assert_eq!(contract.seats[odd].balance, 90 + 13);
+            assert_eq!(contract.seats[even].balance, 90 + 12);
+            contract.check_chips().unwrap();
+        }
+    }
+
+    #[test]
+    fn timeout_checks_for_a_player_who_owes_nothing() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        assert!(contract.claim_timeout(&addr(1)).is_err());
+
+        contract.set_clock(Box::new(MockClock::new(130)));
+        contract.claim_timeout(&addr(1)).unwrap();
+        assert!(contract.seats[0].status == SeatStatus::Active);
+        assert_eq!(contract.next_player, 1);
+        assert_eq!(contract.turn_deadline, 160);
+    }
+
+    #[test]
+    fn timeout_folds_a_player_facing_a_bet() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 0);
+        contract.post_bet(1, 20);
+        contract.post_bet(2, 20);
+        contract.min_bet = 20;
+
+        contract.set_clock(Box::new(MockClock::new(130)));
+        contract.claim_timeout(&addr(1)).unwrap();
+        assert!(contract.seats[0].status == SeatStatus::Folded);
+        assert!(contract.stage == GameStage::Turn);
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn timeout_sits_out_players_who_do_not_commit_or_reveal() {
+        let mut contract = table(3);
+        contract.set_clock(Box::new(MockClock::new(100)));
+        contract.stage = GameStage::Commit;
+        contract.turn_deadline = 130;
+        contract.seats[0].commitment = vec![1; 32];
+        contract.seats[1].commitment = vec![1; 32];
+        assert!(contract.claim_timeout(&addr(0)).is_err());
+
+        contract.set_clock(Box::new(MockClock::new(130)));
+        contract.claim_timeout(&addr(0)).unwrap();
+        assert!(contract.seats[2].status == SeatStatus::SittingOut);
+        assert!(contract.stage == GameStage::Reveal);
+
+        //With only one seed revealed there is nobody left to play against
+        contract.seats[0].reveal = vec![1; 32];
+        contract.set_clock(Box::new(MockClock::new(160)));
+        contract.claim_timeout(&addr(0)).unwrap();
+        assert!(contract.seats[1].status == SeatStatus::SittingOut);
+        assert!(contract.stage == GameStage::Join);
+    }
+
+    #[test]
+    fn only_open_tables_are_listed() {
+        let mut contract = table(3);
+        assert!(contract.is_open());
+
+        contract.tournament_over = true;
+        assert!(!contract.is_open());
+
+        let mut contract = table(3);
+        contract.set_coordinator(1, 0);
+        assert!(!contract.is_open());
+    }
+
+    #[test]
+    fn player_state_requests_cannot_be_replayed() {
+        let mut contract = table(3);
+        contract.set_clock(Box::new(MockClock::new(100)));
+        assert!(contract.get_player_state(&addr(0), 0, 90).is_ok());
+        assert!(contract.get_player_state(&addr(0), 0, 60).is_err());
+        assert!(contract.get_player_state(&addr(0), 0, 110).is_err());
+
+        contract.hand_number = 1;
+        assert!(contract.get_player_state(&addr(0), 0, 100).is_err());
+        assert!(contract.get_player_state(&addr(0), 1, 100).is_ok());
+    }
+
+    #[test]
+    fn moved_player_waits_for_the_big_blind() {
+        let mut contract = table(3);
+        contract.seat_player(Player::new(addr(3), 100)).unwrap();
+        assert!(!contract.seats[3].wait_for_big_blind);
+
+        //Sits out while the big blind is due elsewhere, and is dealt in once it
+        //reaches the new seat
+        for &(big_blind, dealt_in) in [(1, false), (2, true)].iter() {
+            let mut contract = table(3);
+            contract.set_clock(Box::new(MockClock::new(100)));
+            contract.big_blind = big_blind;
+            contract.seat_player(Player::new(addr(3), 100)).unwrap();
+            assert!(contract.seats[3].wait_for_big_blind);
+            contract.play_hand(&addr(0)).unwrap();
+            assert_eq!(contract.seats[3].status.in_hand(), dealt_in);
+        }
+    }
+
+    #[test]
+    fn leaving_before_the_deal_stops_a_heads_up_hand() {
+        for stage in [GameStage::Commit, GameStage::Reveal].iter() {
+            let mut contract = table(2);
+            contract.stage = *stage;
+            assert_eq!(contract.withdraw(&addr(1)).unwrap(), 100);
+            assert!(contract.stage == GameStage::Join);
+            contract.check_chips().unwrap();
         }
     }
+
+    #[test]
+    fn short_all_in_does_not_reopen_the_betting() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        contract.seats[2].balance = 30;
+        contract.take_action(&addr(0), Action::Bet, 20).unwrap();
+        contract.take_action(&addr(1), Action::Call, 0).unwrap();
+        //A raise of 10 is less than the last full raise of 20
+        contract.take_action(&addr(2), Action::AllIn, 0).unwrap();
+        assert_eq!(contract.next_player, 0);
+
+        assert!(contract.take_action(&addr(0), Action::Raise, 50).is_err());
+        assert!(contract.take_action(&addr(0), Action::AllIn, 0).is_err());
+        assert_eq!(
+            legal_actions(&contract, &addr(0)),
+            vec![ActionType::FOLD, ActionType::CALL]
+        );
+
+        contract.take_action(&addr(0), Action::Call, 0).unwrap();
+        contract.take_action(&addr(1), Action::Call, 0).unwrap();
+        assert!(contract.stage == GameStage::Turn);
+    }
+
+    #[test]
+    fn full_all_in_reopens_the_betting() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        contract.seats[2].balance = 40;
+        contract.take_action(&addr(0), Action::Bet, 20).unwrap();
+        contract.take_action(&addr(1), Action::Call, 0).unwrap();
+        contract.take_action(&addr(2), Action::AllIn, 0).unwrap();
+        contract.take_action(&addr(0), Action::Raise, 60).unwrap();
+        assert_eq!(contract.min_bet, 60);
+    }
+
+    #[test]
+    fn only_players_dealt_in_see_the_deck() {
+        let mut contract = table(3);
+        contract.set_clock(Box::new(MockClock::new(100)));
+        contract.history.set_deck(vec![1, 2, 3]);
+        contract.history.set_board(vec![4]);
+        let mut reveal = SeedReveal::new();
+        reveal.set_addr(addr(0).to_string());
+        contract.history.mut_reveals().push(reveal);
+
+        let public = contract.get_public_state(&addr(0)).unwrap();
+        assert!(public.get_history().get_deck().is_empty());
+        assert!(public.get_history().get_reveals().is_empty());
+        assert_eq!(public.get_history().get_board(), &[4][..]);
+
+        let dealt_in = contract.get_player_state(&addr(0), 0, 100).unwrap();
+        assert_eq!(dealt_in.get_history().get_deck(), &[1, 2, 3][..]);
+        let sat_out = contract.get_player_state(&addr(1), 0, 100).unwrap();
+        assert!(sat_out.get_history().get_deck().is_empty());
+    }
+
+    #[test]
+    fn rebuys_at_a_multi_table_tournament_are_left_to_the_coordinator() {
+        let mut config = TournamentConfig::new();
+        config.set_buy_in(100);
+        config.set_starting_stack(1000);
+        config.set_rebuy_levels(1);
+        config.mut_levels().push(BlindLevel::new());
+        config.mut_levels()[0].set_blind(10);
+        let mut options = CreateGameRequest::new();
+        options.set_max_players(6);
+        options.set_time_per_turn(30);
+        options.set_tournament(config);
+        let mut contract = PokerContract::new(1, &options).unwrap();
+        contract.set_coordinator(1, 0);
+        contract.set_clock(Box::new(MockClock::new(100)));
+        contract.seat_player(Player::new(addr(0), 0)).unwrap();
+        contract.hand_number = 1;
+
+        contract.rebuy(&addr(0), 100).unwrap();
+        assert_eq!(contract.seats[0].balance, 1000);
+        assert_eq!(contract.prize_pool, 0);
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn only_seated_players_can_claim_a_timeout() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        contract.on_deck.push(Player::new(addr(3), 100));
+        contract.reindex();
+        contract.set_clock(Box::new(MockClock::new(130)));
+        assert!(contract.claim_timeout(&addr(3)).is_err());
+        contract.claim_timeout(&addr(1)).unwrap();
+    }
+
+    #[test]
+    fn no_limit_raise_is_at_least_the_last_raise() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        contract.last_raise = 10;
+        assert!(contract.take_action(&addr(0), Action::Bet, 5).is_err());
+        contract.take_action(&addr(0), Action::Bet, 20).unwrap();
+        contract.take_action(&addr(1), Action::Raise, 50).unwrap();
+        //The last raise was 30, so the next is to at least 80
+        assert!(contract.take_action(&addr(2), Action::Raise, 70).is_err());
+        contract.take_action(&addr(2), Action::Raise, 80).unwrap();
+        assert_eq!(contract.min_bet, 80);
+        assert_eq!(contract.last_raise, 30);
+    }
+
+    #[test]
+    fn pot_limit_caps_the_total_bet_at_the_pot_after_calling() {
+        let mut contract = table(3);
+        contract.betting_structure = BettingStructure::POT_LIMIT;
+        for i in 0..3 {
+            contract.post_bet(i, 10);
+        }
+        contract.collect_bets();
+        flop(&mut contract, 0, 2);
+        contract.last_raise = 10;
+
+        //A 30 chip pot allows a bet of 30
+        assert!(contract.take_action(&addr(0), Action::Bet, 31).is_err());
+        contract.take_action(&addr(0), Action::Bet, 30).unwrap();
+        //Calling makes the pot 90, so the raise can be to 30 + 90
+        assert!(contract.take_action(&addr(1), Action::Raise, 121).is_err());
+        contract.take_action(&addr(1), Action::Raise, 120).unwrap();
+        assert_eq!(contract.min_bet, 120);
+    }
+
+    #[test]
+    fn fixed_limit_bets_are_one_size_and_capped() {
+        let mut contract = table(3);
+        contract.betting_structure = BettingStructure::FIXED_LIMIT;
+        contract.raise_cap = 2;
+        flop(&mut contract, 0, 2);
+        contract.last_raise = 10;
+
+        assert!(contract.take_action(&addr(0), Action::Bet, 20).is_err());
+        contract.take_action(&addr(0), Action::Bet, 10).unwrap();
+        contract.take_action(&addr(1), Action::Raise, 20).unwrap();
+        assert!(contract.take_action(&addr(2), Action::Raise, 30).is_err());
+        assert!(!legal_actions(&contract, &addr(2)).contains(&ActionType::RAISE));
+        contract.take_action(&addr(2), Action::Call, 0).unwrap();
+    }
+
+    #[test]
+    fn stud_bring_in_is_completed_to_a_full_bet() {
+        let mut contract = table(3);
+        contract.variant = GameVariant::SEVEN_CARD_STUD;
+        contract.betting_structure = BettingStructure::FIXED_LIMIT;
+        flop(&mut contract, 0, 2);
+        contract.stage = GameStage::ThirdStreet;
+        contract.post_bet(2, 5);
+        contract.min_bet = 5;
+        contract.last_raise = 10;
+        contract.raises = 0;
+
+        assert!(contract.take_action(&addr(0), Action::Raise, 15).is_err());
+        contract.take_action(&addr(0), Action::Raise, 10).unwrap();
+        //After the completion, raises go up by a full small bet
+        assert!(contract.take_action(&addr(1), Action::Raise, 15).is_err());
+        contract.take_action(&addr(1), Action::Raise, 20).unwrap();
+        assert_eq!(contract.raises, 2);
+    }
+
+    #[test]
+    fn queued_actions_check_when_nothing_is_owed() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        assert!(contract.queue_action(&addr(0), QueuedAction::CheckFold).is_err());
+        contract.queue_action(&addr(1), QueuedAction::CheckFold).unwrap();
+        contract.queue_action(&addr(2), QueuedAction::CallAny).unwrap();
+
+        contract.take_action(&addr(0), Action::Check, 0).unwrap();
+        assert!(contract.stage == GameStage::Turn);
+        assert!(contract.seats[1].status == SeatStatus::Active);
+    }
+
+    #[test]
+    fn queued_actions_fold_or_call_a_bet() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        contract.queue_action(&addr(1), QueuedAction::CheckFold).unwrap();
+        contract.queue_action(&addr(2), QueuedAction::CallAny).unwrap();
+
+        contract.take_action(&addr(0), Action::Bet, 20).unwrap();
+        assert!(contract.seats[1].status == SeatStatus::Folded);
+        assert_eq!(contract.seats[2].balance, 80);
+        assert!(contract.stage == GameStage::Turn);
+        contract.check_chips().unwrap();
+    }
+
+    #[test]
+    fn legal_amounts_are_total_bets() {
+        let mut contract = table(3);
+        flop(&mut contract, 0, 2);
+        contract.last_raise = 10;
+        contract.take_action(&addr(0), Action::Bet, 20).unwrap();
+        //Seat 1 already has 5 in, so calling adds 15 for a total of 20
+        contract.post_bet(1, 5);
+        let amounts: Vec<(ActionType, u64, u64)> = contract
+            .get_legal_actions(&addr(1))
+            .iter()
+            .map(|action| {
+                (
+                    action.get_action(),
+                    action.get_min_amount(),
+                    action.get_max_amount(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            amounts,
+            vec![
+                (ActionType::FOLD, 0, 0),
+                (ActionType::CALL, 20, 20),
+                (ActionType::RAISE, 40, 100),
+                (ActionType::ALL_IN, 100, 100),
+            ]
+        );
+    }
 }
@@ -0,0 +1,150 @@
+//Blind schedules and payouts for tournament tables, and the bookkeeping shared by
+//tables and the multi-table tournament coordinator.
+use core::cmp::{max, min};
+use ekiden_core_common::{Address, ContractError, Result};
+
+use poker_api::{Finish, Nonce, TournamentConfig};
+use std::collections::HashMap;
+
+//Checks that a tournament can be run with `config`.
+pub fn validate(config: &TournamentConfig) -> Result<()> {
+    if config.get_buy_in() == 0 || config.get_starting_stack() == 0 {
+        return Err(ContractError::new(
+            "A tournament needs a buy-in and a starting stack.",
+        ));
+    }
+    for level in config.get_levels() {
+        if level.get_blind() == 0 || level.get_small_blind_amount() > level.get_blind() {
+            return Err(ContractError::new("Invalid blind level."));
+        }
+    }
+    if config.get_add_on_chips() > 0 && config.get_rebuy_levels() == 0 {
+        return Err(ContractError::new(
+            "Add-ons are sold at the end of the rebuy period.",
+        ));
+    }
+    let payouts = config.get_payouts();
+    if !payouts.is_empty() && payouts.iter().sum::<u64>() != 10000 {
+        return Err(ContractError::new(
+            "Tournament payouts must add up to the whole prize pool.",
+        ));
+    }
+    Ok(())
+}
+
+//Index of the blind level in effect after `hands` hands and `elapsed` seconds. Levels
+//go up by hand count or by time, whichever comes first, and the last level is kept
+//once the schedule runs out.
+pub fn current_level(config: &TournamentConfig, hands: u64, elapsed: u64) -> usize {
+    let mut level = 0;
+    if config.get_hands_per_level() > 0 {
+        level = max(level, hands / config.get_hands_per_level());
+    }
+    if config.get_seconds_per_level() > 0 {
+        level = max(level, elapsed / config.get_seconds_per_level());
+    }
+    min(level as usize, config.get_levels().len() - 1)
+}
+
+//Share of `prize_pool` paid for finishing in `place`, where the winner is in place 1.
+//The winner takes everything if the tournament has no payout table.
+pub fn prize(config: &TournamentConfig, prize_pool: u64, place: u64) -> u64 {
+    let payouts = config.get_payouts();
+    if payouts.is_empty() {
+        return if place == 1 { prize_pool } else { 0 };
+    }
+    match payouts.get(place as usize - 1) {
+        Some(share) => prize_pool * share / 10000,
+        None => 0,
+    }
+}
+
+//Result for a player finishing in `place`, given the `results` recorded so far. The
+//winner also gets any chips left over from rounding the other prizes. Place 0 is a
+//player knocked out at a table of a multi-table tournament, who is placed and paid
+//by the coordinator.
+pub fn finish(
+    config: &TournamentConfig,
+    prize_pool: u64,
+    results: &[Finish],
+    addr: String,
+    place: u64,
+) -> Finish {
+    let amount = if place == 0 {
+        0
+    } else if place == 1 {
+        let paid: u64 = results.iter().map(|result| result.get_prize()).sum();
+        prize_pool - paid
+    } else {
+        prize(config, prize_pool, place)
+    };
+    let mut result = Finish::new();
+    result.set_addr(addr);
+    result.set_place(place);
+    result.set_prize(amount);
+    result
+}
+
+//Records `nonce` as the last one `msg_sender` used. Each request must use the nonce
+//after the last one, so requests cannot be replayed or reordered.
+pub fn check_nonce(
+    nonces: &mut HashMap<String, u64>,
+    msg_sender: &Address,
+    nonce: u64,
+) -> Result<()> {
+    let last_nonce = match nonces.get(&msg_sender.to_string()) {
+        Some(nonce) => *nonce,
+        None => 0,
+    };
+    if nonce <= last_nonce {
+        return Err(ContractError::new("Stale or duplicate request."));
+    }
+    if nonce != last_nonce + 1 {
+        return Err(ContractError::new("Request nonce is out of order."));
+    }
+    nonces.insert(msg_sender.to_string(), nonce);
+    Ok(())
+}
+
+pub fn serialize_nonces(nonces: &HashMap<String, u64>) -> Vec<Nonce> {
+    let mut formatted_nonces: Vec<Nonce> = Vec::new();
+    for (addr, last) in nonces.iter() {
+        let mut nonce = Nonce::new();
+        nonce.set_addr(addr.clone());
+        nonce.set_nonce(*last);
+        formatted_nonces.push(nonce);
+    }
+    return formatted_nonces;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonces_must_go_up_one_at_a_time() {
+        let mut nonces = HashMap::new();
+        let sender = Address::from("player0".to_string());
+        check_nonce(&mut nonces, &sender, 1).unwrap();
+        assert!(check_nonce(&mut nonces, &sender, 1).is_err());
+        assert!(check_nonce(&mut nonces, &sender, 3).is_err());
+        check_nonce(&mut nonces, &sender, 2).unwrap();
+        assert_eq!(serialize_nonces(&nonces)[0].get_nonce(), 2);
+    }
+
+    #[test]
+    fn winner_gets_the_rounding_left_over() {
+        let mut config = TournamentConfig::new();
+        config.set_payouts(vec![5000, 3000, 2000]);
+        let mut results = Vec::new();
+        let third = finish(&config, 101, &results, "third".to_string(), 3);
+        results.push(third);
+        let second = finish(&config, 101, &results, "second".to_string(), 2);
+        results.push(second);
+        let winner = finish(&config, 101, &results, "first".to_string(), 1);
+        assert_eq!(results[0].get_prize(), 20);
+        assert_eq!(results[1].get_prize(), 30);
+        assert_eq!(winner.get_prize(), 51);
+        assert_eq!(finish(&config, 101, &results, "moved".to_string(), 0).get_prize(), 0);
+    }
+}